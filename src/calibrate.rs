@@ -0,0 +1,213 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::anyhow;
+use rdev::{listen, simulate, EventType};
+use toml_edit::{DocumentMut, Item};
+
+use crate::{coord::Scaling, format::Format};
+
+/// 参考点: (配置坐标, 实际坐标)
+type Reference = ((f64, f64), (f64, f64));
+
+/// 校准向导: 根据两个参考点计算 scaling 和 offset, 确认后写回配置文件
+pub fn calibrate(path: &Path) -> anyhow::Result<()> {
+    let cursor = Arc::new(Mutex::new(None));
+    let tracker = cursor.clone();
+    thread::spawn(move || {
+        let _ = listen(move |event| {
+            if let EventType::MouseMove { x, y } = event.event_type {
+                *tracker.lock().unwrap() = Some((x, y));
+            }
+        });
+    });
+
+    println!("依次把鼠标移到两个已知配置坐标的位置(如截图中的左上角和右下角, 两点不能在同一行或同一列)");
+    let first = reference(1, &cursor)?;
+    let second = reference(2, &cursor)?;
+    let (scaling, offset) = solve(first, second)?;
+    println!(
+        "计算结果: scaling = {}, offset = [{}, {}]",
+        scaling_text(scaling),
+        offset.0,
+        offset.1
+    );
+
+    // 按计算结果移动鼠标到参考点, 由用户确认
+    for (index, ((x, y), _)) in [first, second].into_iter().enumerate() {
+        let to = ((x + offset.0) / scaling.x(), (y + offset.1) / scaling.y());
+        simulate(&EventType::MouseMove { x: to.0, y: to.1 }).map_err(|err| anyhow!("移动鼠标失败: {err:?}"))?;
+        if !confirm(&format!("鼠标是否移到了参考点 {} ({x}, {y})? [Y/n] ", index + 1))? {
+            return Err(anyhow!("校准结果不正确, 没有写入配置文件"));
+        }
+    }
+
+    write(path, scaling, offset)
+}
+
+/// 记录参考点, 输入无效时重新输入
+fn reference(index: usize, cursor: &Mutex<Option<(f64, f64)>>) -> anyhow::Result<Reference> {
+    loop {
+        let line = input(&format!(
+            "参考点 {index}: 移动鼠标到该位置, 然后输入它的配置坐标 x y 并回车: "
+        ))?;
+        let Some(point) = parse_point(&line) else {
+            println!("无效的坐标 {:?}, 格式如: 1920 1080", line.trim());
+            continue;
+        };
+        match *cursor.lock().unwrap() {
+            Some(actual) => return Ok((point, actual)),
+            None => println!("没有获取到鼠标位置, 请移动鼠标后重新输入"),
+        }
+    }
+}
+
+/// 解析 "x y" 或 "x, y"
+fn parse_point(line: &str) -> Option<(f64, f64)> {
+    let mut parts = line.split([' ', ',']).filter(|part| !part.is_empty());
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some((x, y))
+}
+
+/// 根据 实际坐标 = (配置坐标 + offset) / scaling 求解, 两个方向缩放接近时合并为统一缩放
+fn solve(
+    ((x1, y1), (ax1, ay1)): Reference,
+    ((x2, y2), (ax2, ay2)): Reference,
+) -> anyhow::Result<(Scaling, (f64, f64))> {
+    if (ax2 - ax1).abs() < 1.0 || (ay2 - ay1).abs() < 1.0 {
+        return Err(anyhow!("两个参考点距离太近或在同一行、同一列"));
+    }
+    let sx = (x2 - x1) / (ax2 - ax1);
+    let sy = (y2 - y1) / (ay2 - ay1);
+    if sx <= 0.0 || sy <= 0.0 {
+        return Err(anyhow!("参考点的配置坐标与实际位置方向相反, 请检查输入"));
+    }
+
+    let round = |value: f64, digits: i32| (value * 10_f64.powi(digits)).round() / 10_f64.powi(digits);
+    let scaling = match (sx - sy).abs() < 0.01 {
+        true => Scaling::Uniform(round((sx + sy) / 2.0, 3)),
+        false => Scaling::Axes(round(sx, 3), round(sy, 3)),
+    };
+    let offset = (
+        round((ax1 * scaling.x() - x1 + ax2 * scaling.x() - x2) / 2.0, 0),
+        round((ay1 * scaling.y() - y1 + ay2 * scaling.y() - y2) / 2.0, 0),
+    );
+    Ok((scaling, offset))
+}
+
+/// 写回配置文件, toml 保留注释和格式, 其他格式按原顺序重写(yaml ron 会丢失注释)
+fn write(path: &Path, scaling: Scaling, offset: (f64, f64)) -> anyhow::Result<()> {
+    let data = fs::read_to_string(path).map_err(|err| anyhow!("{}: {err}", path.display()))?;
+    let data = match Format::from_path(path)? {
+        Format::Toml => {
+            let mut doc: DocumentMut = data.parse().map_err(|err| anyhow!("{}: {err}", path.display()))?;
+            let scaling = match scaling {
+                Scaling::Uniform(scaling) => toml_edit::value(scaling),
+                Scaling::Axes(x, y) => toml_edit::value(toml_edit::Array::from_iter([x, y])),
+            };
+            set_toml(&mut doc, "scaling", scaling);
+            set_toml(
+                &mut doc,
+                "offset",
+                toml_edit::value(toml_edit::Array::from_iter([offset.0 as i64, offset.1 as i64])),
+            );
+            doc.to_string()
+        }
+        format => {
+            if format != Format::Json {
+                println!("{format:?} 格式按原顺序重写, 不会保留注释");
+            }
+            let mut value: serde_json::Value = format.deserialize(&data)?;
+            value["scaling"] = serde_json::to_value(scaling)?;
+            value["offset"] = serde_json::to_value(offset)?;
+            format.serialize(&value)?
+        }
+    };
+    fs::write(path, data).map_err(|err| anyhow!("{}: {err}", path.display()))?;
+    println!("已写入 {}", path.display());
+    Ok(())
+}
+
+/// 替换值并保留原值后面的注释
+fn set_toml(doc: &mut DocumentMut, key: &str, mut item: Item) {
+    if let (Some(prev), Some(value)) = (doc.get(key).and_then(Item::as_value), item.as_value_mut()) {
+        *value.decor_mut() = prev.decor().clone();
+    }
+    doc[key] = item;
+}
+
+fn scaling_text(scaling: Scaling) -> String {
+    match scaling {
+        Scaling::Uniform(scaling) => scaling.to_string(),
+        Scaling::Axes(x, y) => format!("[{x}, {y}]"),
+    }
+}
+
+fn input(prompt: &str) -> io::Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line)
+}
+
+fn confirm(prompt: &str) -> io::Result<bool> {
+    let line = input(prompt)?;
+    Ok(matches!(line.trim().to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solve_uniform_scaling() {
+        // 实际坐标 = (配置坐标 + (10, 20)) / 2
+        let (scaling, offset) = solve(((90.0, 80.0), (50.0, 50.0)), ((1990.0, 1080.0), (1000.0, 550.0))).unwrap();
+        assert_eq!(scaling, Scaling::Uniform(2.0));
+        assert_eq!(offset, (10.0, 20.0));
+    }
+
+    #[test]
+    fn solve_axes_scaling() {
+        let (scaling, offset) = solve(((100.0, 150.0), (50.0, 100.0)), ((300.0, 300.0), (150.0, 200.0))).unwrap();
+        assert_eq!(scaling, Scaling::Axes(2.0, 1.5));
+        assert_eq!(offset, (0.0, 0.0));
+    }
+
+    #[test]
+    fn solve_rejects_bad_points() {
+        assert!(solve(((0.0, 0.0), (100.0, 0.0)), ((100.0, 100.0), (100.5, 100.0))).is_err());
+        assert!(solve(((0.0, 0.0), (100.0, 100.0)), ((100.0, 100.0), (0.0, 200.0))).is_err());
+    }
+
+    #[test]
+    fn parse_points() {
+        assert_eq!(parse_point("1920 1080"), Some((1920.0, 1080.0)));
+        assert_eq!(parse_point(" 10.5,  20 "), Some((10.5, 20.0)));
+        assert_eq!(parse_point("10"), None);
+        assert_eq!(parse_point("10 20 30"), None);
+        assert_eq!(parse_point("x 20"), None);
+    }
+
+    #[test]
+    fn write_yaml() {
+        let path = std::env::temp_dir().join(format!("ms-calibrate-{}.yaml", std::process::id()));
+        fs::write(&path, "delay: 20\nscaling: 1\noffset: [0, 0]\nstart: many\n").unwrap();
+        write(&path, Scaling::Axes(1.25, 1.5), (10.0, -5.0)).unwrap();
+        let data = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let value: serde_json::Value = Format::Yaml.deserialize(&data).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "delay": 20, "scaling": [1.25, 1.5], "offset": [10.0, -5.0], "start": "many" })
+        );
+        assert!(data.starts_with("delay:"));
+    }
+}
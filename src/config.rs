@@ -1,45 +1,163 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fs, mem,
-    path::Path,
-    sync::Arc,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt, mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use anyhow::anyhow;
 use rdev::{Button, EventType, Key};
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
-    script::{Custom, Method, Script, ScriptList, Trigger},
+    coord::{Coord, Position, Scaling},
+    display::{self, Display},
+    format,
+    keys::{self, deserialize_button, deserialize_key, deserialize_keys, Input},
+    motion::Curve,
+    schema::{ButtonSchema, KeySchema, KeysSchema, TriggersSchema},
+    script::{
+        deserialize_triggers, Context, Custom, Hook, Hooks, Hover, Interceptor, Method, Remap, Script, ScriptList,
+        Title, Trigger, TriggerOn,
+    },
     start::Restart,
     window::WindowList,
 };
 
+/// 编译后的命名脚本块
+type CompiledBlocks = HashMap<String, Arc<Vec<Method>>>;
+/// 按显示器编号缓存的已编译脚本块(脚本指定 display 时引用的脚本块也使用该显示器)
+type BlockCache = HashMap<Option<usize>, CompiledBlocks>;
+
 /// 脚本配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     /// 延迟
     pub delay: u64,
-    /// 缩放
-    pub scaling: f64,
+    /// 缩放(数字或 [x, y])
+    pub scaling: Scaling,
     /// 偏移位置
     pub offset: (f64, f64),
+    /// 坐标所在显示器编号(见 displays 命令), 指定后使用该显示器的偏移和缩放
+    #[serde(default)]
+    pub display: Option<usize>,
     /// 窗口位置
     pub point: (f64, f64),
     /// 字体大小
     pub font_size: f64,
     /// 字体颜色
     pub font_color: (u8, u8, u8),
+    /// 平滑移动时每秒移动事件数
+    #[serde(default = "default_move_rate")]
+    pub move_rate: u64,
     /// 脚本列表
+    #[serde(default)]
     pub scripts: Vec<ScriptConfig>,
     /// 启动配置
     pub start: Restart,
     /// 脚本块
     #[serde(default)]
-    pub blocks: HashMap<String, Vec<MethodConfig>>,
+    pub blocks: HashMap<String, BlockConfig>,
+    /// 启动时执行(开始监听前)
+    #[serde(default)]
+    pub on_start: Vec<HookConfig>,
+    /// 退出时执行
+    #[serde(default)]
+    pub on_exit: Vec<HookConfig>,
+    /// 包含其他配置文件中的脚本和脚本块(相对当前文件路径, 支持通配符)
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// 按键重映射: 原按键 = 目标按键或快捷键字符串(拦截原按键, 需要重启生效)
+    #[serde(default)]
+    pub remap: HashMap<String, String>,
+    /// 配置文件路径
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// 脚本块所在文件
+    #[serde(skip)]
+    pub block_sources: HashMap<String, PathBuf>,
+}
+
+/// 被包含的配置文件, 只能定义脚本和脚本块
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IncludeConfig {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    scripts: Vec<ScriptConfig>,
+    #[serde(default)]
+    blocks: HashMap<String, BlockConfig>,
+    #[serde(skip)]
+    block_sources: HashMap<String, PathBuf>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl IncludeConfig {
+    /// 合并被包含的文件及其包含的文件, 已合并过的文件会被跳过
+    fn merge_file(&mut self, path: &Path, visited: &mut HashSet<PathBuf>) -> anyhow::Result<()> {
+        if !visited.insert(path.canonicalize()?) {
+            return Ok(());
+        }
+        let include: Self = format::read(path)?;
+        for file in resolve_include(path, &include.include)? {
+            self.merge_file(&file, visited)?;
+        }
+        self.merge(include, path)
+    }
+
+    /// 合并脚本和脚本块, 标题或名称重复时报错
+    fn merge(&mut self, other: Self, path: &Path) -> anyhow::Result<()> {
+        for mut script in other.scripts {
+            if let Some(prev) = self.scripts.iter().find(|item| item.title == script.title) {
+                return Err(anyhow!(
+                    "{}: title {:?} 与 {} 中的脚本重复",
+                    path.display(),
+                    script.title,
+                    prev.source.display()
+                ));
+            }
+            script.source = path.to_path_buf();
+            self.scripts.push(script);
+        }
+        for (name, block) in other.blocks {
+            if let Some(prev) = self.block_sources.get(&name) {
+                return Err(anyhow!(
+                    "{}: block {name:?} 与 {} 中的 block 重复",
+                    path.display(),
+                    prev.display()
+                ));
+            }
+            self.block_sources.insert(name.clone(), path.to_path_buf());
+            self.blocks.insert(name, block);
+        }
+        Ok(())
+    }
+}
+
+/// 解析 include 路径(相对 path 所在目录), 通配符匹配结果按路径排序
+fn resolve_include(path: &Path, include: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut files = vec![];
+    for pattern in include {
+        let full = dir.join(pattern);
+        let mut matched: Vec<PathBuf> = glob::glob(&full.to_string_lossy())
+            .map_err(|err| anyhow!("{}: include {pattern:?} 无效: {err}", path.display()))?
+            .collect::<Result<_, _>>()?;
+        if matched.is_empty() && !pattern.contains(['*', '?', '[']) {
+            return Err(anyhow!("{}: include 文件 {pattern:?} 不存在", path.display()));
+        }
+        matched.sort();
+        files.extend(matched);
+    }
+    Ok(files)
+}
+
+/// 脚本
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ScriptConfig {
     /// 脚本标题
     pub title: String,
@@ -50,118 +168,550 @@ pub struct ScriptConfig {
     /// 单独配置延迟
     pub delay: Option<u64>,
 
-    /// 触发按键
+    /// 单独配置坐标所在显示器
+    #[serde(default)]
+    pub display: Option<usize>,
+
+    /// 触发按键(列表或快捷键字符串, 如 "Ctrl+Shift+S")
+    #[serde(deserialize_with = "deserialize_triggers")]
+    #[schemars(with = "TriggersSchema")]
     pub trigger: Vec<Trigger>,
 
+    /// 精确匹配修饰键: 按住触发按键以外的修饰键(Ctrl Shift Alt Win)时不触发
+    #[serde(default)]
+    pub exact: bool,
+
+    /// 触发时机: press 按下时; release 全部松开时
+    #[serde(default)]
+    pub on: TriggerOn,
+
+    /// 拦截触发脚本的滚轮事件(不再滚动页面), 只能用于滚轮触发, 需要启动时就存在
+    #[serde(default)]
+    pub consume: bool,
+
     /// 脚本事件
     pub methods: Vec<MethodConfig>,
+
+    /// 脚本所在文件
+    #[serde(skip)]
+    pub source: PathBuf,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 钩子引用的脚本或脚本块
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub enum HookConfig {
+    /// 脚本标题
+    Script(String),
+    /// 脚本块名称
+    Block(String),
+}
+
+/// 脚本方法
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MethodConfig {
     #[serde(flatten)]
     event: ScriptEvent,
+    /// 执行后等待时间(ms)
     #[serde(rename = "Await")]
     await_: Option<u64>,
+    /// 按住时间(ms), 代替按下和松开之间的脚本延迟, 只能用于 Key Keys Click ClickOn ClickAt
+    hold: Option<u64>,
 }
 
 impl Config {
     pub fn parse<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let data = fs::read_to_string(path)?;
-        let config: Self = toml::from_str(&data)?;
+        let path = path.as_ref();
+        let mut config: Self = format::read(path)?;
+        config.path = path.to_path_buf();
 
-        let hs: HashSet<&str> = config.scripts.iter().map(|m| m.title.as_str()).collect();
-        if config.scripts.len() != hs.len() {
-            return Err(anyhow!("title 不可重复"));
+        // 被包含的文件按 include 顺序先合并, 主配置文件最后合并
+        let mut merged = IncludeConfig::default();
+        let mut visited = HashSet::from([path.canonicalize()?]);
+        for file in resolve_include(path, &config.include)? {
+            merged.merge_file(&file, &mut visited)?;
         }
+        let main = IncludeConfig {
+            scripts: mem::take(&mut config.scripts),
+            blocks: mem::take(&mut config.blocks),
+            ..Default::default()
+        };
+        merged.merge(main, path)?;
 
-        Ok(toml::from_str(&data)?)
+        config.scripts = merged.scripts;
+        config.blocks = merged.blocks;
+        config.block_sources = merged.block_sources;
+        Ok(config)
     }
 
-    pub fn load(mut self) -> anyhow::Result<(ScriptList, WindowList)> {
-        let mut scripts = vec![];
-        mem::swap(&mut self.scripts, &mut scripts);
-
+    pub fn load(self, context: Context) -> anyhow::Result<(ScriptList, WindowList)> {
         let window_list = WindowList::init(self.point, self.font_size, self.font_color);
-        let script_list: anyhow::Result<Vec<Script>> = scripts
-            .into_iter()
+        let updater = window_list.updater.clone();
+        let interceptor = Interceptor::new(self.remaps()?);
+        let script_list = ScriptList {
+            scripts: self.to_scripts(&updater, &context)?,
+            path: self.path,
+            updater,
+            context,
+            paused: false,
+            interceptor: Arc::new(Mutex::new(interceptor)),
+        };
+
+        Ok((script_list, window_list))
+    }
+
+    /// 编译按键重映射, 不区分左右的修饰键作为原按键时两侧都重映射
+    fn remaps(&self) -> anyhow::Result<Vec<Remap>> {
+        let mut remaps: Vec<Remap> = vec![];
+        for (from, to) in self.remap.iter() {
+            let err = |err: anyhow::Error| anyhow!("重映射 {from:?}: {err}");
+            let to = keys::parse_accelerator(to)
+                .and_then(|inputs| {
+                    inputs
+                        .into_iter()
+                        .map(Input::events)
+                        .collect::<anyhow::Result<Vec<_>>>()
+                })
+                .map_err(err)?;
+            let froms = match keys::parse_input(from).map_err(err)? {
+                Input::Modifier(modifier) => modifier
+                    .keys()
+                    .map(|key| (EventType::KeyPress(key), EventType::KeyRelease(key)))
+                    .to_vec(),
+                input => vec![input.events().map_err(err)?],
+            };
+            for from in froms {
+                if remaps.iter().any(|remap| remap.from == from) {
+                    return Err(anyhow!("重映射 {from:?}: 原按键重复"));
+                }
+                remaps.push(Remap { from, to: to.clone() });
+            }
+        }
+        Ok(remaps)
+    }
+
+    /// 编译脚本列表
+    pub fn to_scripts(&self, updater: &UnboundedSender<Title>, context: &Context) -> anyhow::Result<Vec<Script>> {
+        let mut cache = BlockCache::new();
+        self.blocks_for(None, &mut cache)?;
+        let scripts = self
+            .scripts
+            .iter()
             .map(|item| {
+                let (methods, hover) = self
+                    .display(item.display)
+                    .and_then(|display| {
+                        let blocks = self.blocks_for(item.display, &mut cache)?;
+                        Ok((
+                            self.to_methods(item.methods.clone(), blocks, display)?,
+                            self.hover(&item.trigger, display)?,
+                        ))
+                    })
+                    .map_err(|err| anyhow!("{}: 脚本 {:?}: {err}", item.source.display(), item.title))?;
+                if item.consume && !item.trigger.iter().any(|trigger| matches!(trigger, Trigger::Wheel(_))) {
+                    return Err(anyhow!(
+                        "{}: 脚本 {:?} 的 consume 只能用于滚轮触发",
+                        item.source.display(),
+                        item.title
+                    ));
+                }
+                if item.trigger.len() > 1 && item.trigger.iter().any(Trigger::is_standalone) {
+                    return Err(anyhow!(
+                        "{}: 脚本 {:?} 的 Corner Region Typed 触发不能与其他触发组合",
+                        item.source.display(),
+                        item.title
+                    ));
+                }
+                if item
+                    .trigger
+                    .iter()
+                    .any(|trigger| matches!(trigger, Trigger::Typed(typed) if typed.text().is_empty()))
+                {
+                    return Err(anyhow!(
+                        "{}: 脚本 {:?} 的 Typed 缩写不能为空",
+                        item.source.display(),
+                        item.title
+                    ));
+                }
                 Ok(Script {
-                    title: Arc::new(item.title),
+                    title: Arc::new(item.title.clone()),
                     delay: item.delay.unwrap_or(self.delay),
-                    trigger: item.trigger.into_iter().map(|m| (m, false)).collect(),
+                    trigger: item.trigger.clone(),
+                    exact: item.exact,
+                    on: item.on,
+                    consume: item.consume,
+                    armed: false,
+                    hover,
                     repeat: item.repeat,
                     task: None,
-                    methods: Arc::new(self.to_methods(item.methods)?),
-                    updater: window_list.updater.clone(),
+                    methods: Arc::new(methods),
+                    updater: updater.clone(),
+                    context: context.clone(),
+                    done: None,
                 })
             })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.check_waits(&scripts)?;
+        Ok(scripts)
+    }
+
+    /// 检查脚本之间等待结束的调用(包括脚本块中的)是否会永远不结束: 等待 repeat = 0 的脚本或互相等待
+    fn check_waits(&self, scripts: &[Script]) -> anyhow::Result<()> {
+        let graph: HashMap<&str, Vec<&str>> = scripts
+            .iter()
+            .map(|item| {
+                (
+                    item.title.as_str(),
+                    item.methods.iter().flat_map(Method::waited).collect(),
+                )
+            })
+            .collect();
+        let endless: HashSet<&str> = scripts
+            .iter()
+            .filter(|item| item.repeat == 0)
+            .map(|item| item.title.as_str())
             .collect();
+        for (config, item) in self.scripts.iter().zip(scripts) {
+            if let Some(title) = graph[item.title.as_str()].iter().find(|title| endless.contains(*title)) {
+                return Err(anyhow!(
+                    "{}: 脚本 {:?} 等待 repeat = 0 的脚本 {title:?} 结束, 会一直等待",
+                    config.source.display(),
+                    item.title
+                ));
+            }
+        }
+        let mut checked = HashSet::new();
+        for item in scripts {
+            if let Some(cycle) = wait_cycle(&graph, &item.title, &mut vec![], &mut checked) {
+                let source = self
+                    .scripts
+                    .iter()
+                    .find(|script| script.title == cycle[0])
+                    .map_or(&self.path, |script| &script.source);
+                return Err(anyhow!(
+                    "{}: 脚本等待结束的调用存在循环: {}",
+                    source.display(),
+                    cycle.join(" → ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 编译启动和退出钩子
+    pub fn hooks(&self) -> anyhow::Result<Hooks> {
+        let mut cache = BlockCache::new();
+        self.blocks_for(None, &mut cache)?;
+        let mut to_hooks = |hooks: &[HookConfig]| -> anyhow::Result<Vec<Hook>> {
+            hooks.iter().map(|hook| self.to_hook(hook, &mut cache)).collect()
+        };
+        Ok(Hooks {
+            on_start: to_hooks(&self.on_start)?,
+            on_exit: to_hooks(&self.on_exit)?,
+        })
+    }
+
+    fn to_hook(&self, hook: &HookConfig, cache: &mut BlockCache) -> anyhow::Result<Hook> {
+        let hook = match hook {
+            HookConfig::Script(title) => {
+                let script = self
+                    .scripts
+                    .iter()
+                    .find(|item| &item.title == title)
+                    .ok_or_else(|| anyhow!("没有找到标题为 {title:?} 的脚本"))?;
+                if script.repeat == 0 {
+                    return Err(anyhow!("钩子不能引用 repeat = 0 的脚本 {title:?}"));
+                }
+                Hook {
+                    delay: script.delay.unwrap_or(self.delay),
+                    repeat: script.repeat,
+                    methods: Arc::new(self.script_methods(script, cache)?),
+                }
+            }
+            HookConfig::Block(name) if matches!(self.blocks.get(name), Some(BlockConfig::Params { .. })) => {
+                return Err(anyhow!("钩子不能引用带参数的 block {name:?}"));
+            }
+            HookConfig::Block(name) => Hook {
+                delay: self.delay,
+                repeat: 1,
+                methods: compiled_block(self.blocks_for(None, cache)?, name)?,
+            },
+        };
+        self.check_finite(&hook.methods, cache, &mut HashSet::new())?;
+        Ok(hook)
+    }
+
+    fn script_methods(&self, script: &ScriptConfig, cache: &mut BlockCache) -> anyhow::Result<Vec<Method>> {
+        self.to_methods(
+            script.methods.clone(),
+            self.blocks_for(script.display, cache)?,
+            self.display(script.display)?,
+        )
+    }
+
+    /// 钩子执行完才开始监听, 不能包含 repeat = 0 的 block, 也不能等待不会结束的脚本(包括被等待脚本中的调用)
+    fn check_finite<'a>(
+        &'a self,
+        methods: &[Method],
+        cache: &mut BlockCache,
+        checked: &mut HashSet<&'a str>,
+    ) -> anyhow::Result<()> {
+        if methods.iter().any(Method::endless) {
+            return Err(anyhow!("钩子中不能使用 repeat = 0 的 block"));
+        }
+        for title in methods.iter().flat_map(Method::waited) {
+            let Some(script) = self.scripts.iter().find(|item| item.title == title) else {
+                continue;
+            };
+            if script.repeat == 0 {
+                return Err(anyhow!("钩子不能等待 repeat = 0 的脚本 {title:?} 结束"));
+            }
+            if checked.insert(&script.title) {
+                let methods = self.script_methods(script, cache)?;
+                self.check_finite(&methods, cache, checked)
+                    .map_err(|err| anyhow!("等待的脚本 {title:?}: {err}"))?;
+            }
+        }
+        Ok(())
+    }
 
-        Ok((ScriptList(script_list?), window_list))
+    /// 编译所有命名脚本块, 每个脚本块只编译一次并在引用处共享
+    fn compile_blocks(&self, display: Option<Display>) -> anyhow::Result<CompiledBlocks> {
+        let mut blocks = CompiledBlocks::new();
+        let mut names: Vec<&String> = self.blocks.keys().collect();
+        names.sort();
+        for name in names {
+            self.compile_block(name, display, &mut blocks, &mut vec![])?;
+        }
+        Ok(blocks)
+    }
+
+    /// 按脚本的显示器编译脚本块, 同一显示器只编译一次
+    fn blocks_for<'a>(&self, id: Option<usize>, cache: &'a mut BlockCache) -> anyhow::Result<&'a CompiledBlocks> {
+        let id = id.or(self.display);
+        Ok(match cache.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.compile_blocks(id.map(display::display).transpose()?)?),
+        })
     }
 
-    pub fn mouse_move(&self, x: f64, y: f64) -> EventType {
-        let x = (x + self.offset.0) / self.scaling;
-        let y = (y + self.offset.1) / self.scaling;
-        EventType::MouseMove { x, y }
+    /// 先编译依赖的脚本块, path 记录当前引用路径用于检测循环引用
+    fn compile_block<'a>(
+        &'a self,
+        name: &'a str,
+        display: Option<Display>,
+        blocks: &mut CompiledBlocks,
+        path: &mut Vec<&'a str>,
+    ) -> anyhow::Result<()> {
+        if blocks.contains_key(name) {
+            return Ok(());
+        }
+        if let Some(index) = path.iter().position(|n| *n == name) {
+            let cycle: Vec<&str> = path[index..].iter().copied().chain([name]).collect();
+            return Err(anyhow!("block 存在循环引用: {}", cycle.join(" → ")));
+        }
+        let block = self
+            .blocks
+            .get(name)
+            .ok_or_else(|| anyhow!("没有找到名为 {name:?} 的 block"))?;
+
+        path.push(name);
+        for dep in block.block_names() {
+            self.compile_block(dep, display, blocks, path)?;
+        }
+        path.pop();
+
+        // 带参数的脚本块在引用处展开
+        if let BlockConfig::Methods(methods) = block {
+            let methods = self.to_methods(methods.clone(), blocks, display).map_err(|err| {
+                let source = self.block_sources.get(name).unwrap_or(&self.path);
+                anyhow!("{}: block {name:?}: {err}", source.display())
+            })?;
+            blocks.insert(name.to_string(), Arc::new(methods));
+        }
+        Ok(())
     }
 
-    fn to_methods(&self, methods: Vec<MethodConfig>) -> anyhow::Result<Vec<Method>> {
+    /// 坐标所在显示器, 脚本未指定时使用全局配置
+    fn display(&self, id: Option<usize>) -> anyhow::Result<Option<Display>> {
+        id.or(self.display).map(display::display).transpose()
+    }
+
+    /// 配置坐标的缩放和偏移, 指定显示器时使用该显示器的缩放和偏移
+    fn transform(&self, display: Option<Display>) -> (Scaling, (f64, f64)) {
+        match display {
+            Some(display) => (Scaling::Uniform(display.scale), display.offset()),
+            None => (self.scaling, self.offset),
+        }
+    }
+
+    /// 配置坐标转换为鼠标位置
+    fn position(&self, x: Coord, y: Coord, display: Option<Display>) -> Position {
+        let (scaling, offset) = self.transform(display);
+        Position {
+            x,
+            y,
+            scaling,
+            offset,
+            display: display.map(|display| display.id),
+        }
+    }
+
+    /// 配置坐标下的矩形区域 [x, y, w, h] 的左上角和右下角, 运行时按当前屏幕计算
+    fn corners(&self, [x, y, w, h]: [Coord; 4], display: Option<Display>) -> (Position, Position) {
+        (self.position(x, y, display), self.position(x + w, y + h, display))
+    }
+
+    /// 鼠标停留触发的区域
+    fn hover(&self, trigger: &[Trigger], display: Option<Display>) -> anyhow::Result<Option<Hover>> {
+        let (region, dwell_ms) = match trigger {
+            [Trigger::Corner { which, dwell_ms }] => (which.region(), *dwell_ms),
+            [Trigger::Region { x, y, w, h, dwell_ms }] => ([*x, *y, *w, *h], *dwell_ms),
+            _ => return Ok(None),
+        };
+        Ok(Some(Hover::new(
+            self.corners(region, display),
+            Duration::from_millis(dwell_ms),
+        )))
+    }
+
+    /// 实际坐标转换为配置坐标(mouse_move 的逆变换), display 为空时使用全局配置
+    pub fn inverse(&self, (x, y): (f64, f64), display: Option<usize>) -> anyhow::Result<(f64, f64)> {
+        let (scaling, offset) = self.transform(self.display(display)?);
+        Ok((x * scaling.x() - offset.0, y * scaling.y() - offset.1))
+    }
+
+    /// 移动鼠标, 依赖屏幕大小的坐标在运行时计算
+    pub fn mouse_move(&self, x: Coord, y: Coord, display: Option<Display>) -> Method {
+        let position = self.position(x, y, display);
+        match position.is_absolute() {
+            true => {
+                let (x, y) = position.resolve();
+                Method::Event(EventType::MouseMove { x, y })
+            }
+            false => Method::Custom(Custom::Move(position)),
+        }
+    }
+
+    /// 平滑移动, duration 为 0 时直接移动
+    pub fn mouse_move_to(
+        &self,
+        (x, y): (Coord, Coord),
+        duration: u64,
+        curve: Curve,
+        rate: Option<u64>,
+        display: Option<Display>,
+    ) -> Method {
+        if duration == 0 {
+            return self.mouse_move(x, y, display);
+        }
+        let rate = rate.unwrap_or(self.move_rate);
+        Method::Custom(Custom::MoveTo { to: self.position(x, y, display), duration, curve, rate })
+    }
+
+    /// 相对移动只需要缩放, 不需要偏移
+    pub fn mouse_move_by(&self, dx: f64, dy: f64, display: Option<Display>) -> Method {
+        let (scaling, _) = self.transform(display);
+        Method::Custom(Custom::MoveBy(dx / scaling.x(), dy / scaling.y()))
+    }
+
+    fn to_methods(
+        &self,
+        methods: Vec<MethodConfig>,
+        blocks: &CompiledBlocks,
+        display: Option<Display>,
+    ) -> anyhow::Result<Vec<Method>> {
         let mut res = vec![];
         for method in methods {
+            let hold = method.hold;
+            if hold.is_some() && !method.event.holdable() {
+                return Err(anyhow!("hold 只能用于 Key Keys Click ClickOn ClickAt"));
+            }
             match method.event {
                 ScriptEvent::ClickDown(button) => res.push(Method::mouse_down(button)),
                 ScriptEvent::ClickUp(button) => res.push(Method::mouse_up(button)),
-                ScriptEvent::Click(button) => {
-                    res.push(Method::mouse_down(button));
-                    res.push(Method::mouse_up(button));
-                }
+                ScriptEvent::Click(button) => res.extend(Method::click(button, hold)),
                 ScriptEvent::ClickOn(button, x, y) => {
-                    res.push(Method::Event(self.mouse_move(x, y)));
-                    res.push(Method::mouse_down(button));
-                    res.push(Method::mouse_up(button));
+                    res.push(self.mouse_move(x, y, display));
+                    res.extend(Method::click(button, hold));
                 }
-                ScriptEvent::ClickTo(button, x, y, x2, y2) => {
-                    res.push(Method::Event(self.mouse_move(x, y)));
+                ScriptEvent::ClickTo(drag) => {
+                    let (button, x, y, x2, y2, duration, curve) = match drag {
+                        Drag::Points(button, x, y, x2, y2) => (button, x, y, x2, y2, 0, Curve::Linear),
+                        Drag::Timed { button, x, y, x2, y2, duration, curve } => {
+                            (button, x, y, x2, y2, duration, curve)
+                        }
+                    };
+                    res.push(self.mouse_move(x, y, display));
                     res.push(Method::mouse_down(button));
-                    res.push(Method::Event(self.mouse_move(x2, y2)));
+                    res.push(self.mouse_move_to((x2, y2), duration, curve, None, display));
                     res.push(Method::mouse_up(button));
                 }
                 ScriptEvent::KeyDown(key) => res.push(Method::key_down(key)),
                 ScriptEvent::KeyUp(key) => res.push(Method::key_up(key)),
-                ScriptEvent::Key(key) => {
-                    res.push(Method::key_down(key));
-                    res.push(Method::key_up(key));
-                }
+                ScriptEvent::Key(key) => res.extend(Method::key(key, hold)),
+                ScriptEvent::Hold { key, ms } => res.extend(Method::key(key, Some(ms))),
                 ScriptEvent::Keys(keys) => {
-                    keys.iter().for_each(|key| res.push(Method::key_down(*key)));
-                    keys.iter().for_each(|key| res.push(Method::key_up(*key)));
+                    let (keys, order) = match keys {
+                        KeyList::Keys(keys) => (keys, ReleaseOrder::Same),
+                        KeyList::Ordered { keys, release_order } => (keys, release_order),
+                    };
+                    let downs = keys.iter().map(|key| EventType::KeyPress(*key));
+                    let ups = keys.iter().map(|key| EventType::KeyRelease(*key));
+                    match order {
+                        ReleaseOrder::Simultaneous => {
+                            res.push(Method::Press { events: downs.collect(), hold });
+                            res.push(Method::Press { events: ups.collect(), hold: None });
+                        }
+                        order => {
+                            // 按住时间只作用于最后一个按下的按键
+                            let mut downs: Vec<Method> = downs.map(Method::Event).collect();
+                            if let (Some(key), Some(_)) = (keys.last(), hold) {
+                                *downs.last_mut().unwrap() =
+                                    Method::Press { events: vec![EventType::KeyPress(*key)], hold };
+                            }
+                            res.extend(downs);
+                            match order {
+                                ReleaseOrder::Reverse => res.extend(ups.rev().map(Method::Event)),
+                                _ => res.extend(ups.map(Method::Event)),
+                            }
+                        }
+                    }
                 }
                 ScriptEvent::Scroll(delta_x, delta_y) => res.push(Method::Event(EventType::Wheel { delta_x, delta_y })),
-                ScriptEvent::Move(x, y) => res.push(Method::Event(self.mouse_move(x, y))),
+                ScriptEvent::Move(x, y) => res.push(self.mouse_move(x, y, display)),
+                ScriptEvent::MoveBy(dx, dy) => res.push(self.mouse_move_by(dx, dy, display)),
+                ScriptEvent::MoveTo { x, y, duration, curve, rate } => {
+                    res.push(self.mouse_move_to((x, y), duration, curve, rate, display))
+                }
+                ScriptEvent::ClickAt { button, dx, dy } => {
+                    res.push(self.mouse_move_by(dx, dy, display));
+                    res.extend(Method::click(button, hold));
+                }
                 ScriptEvent::Sleep(n) => res.push(Method::Custom(Custom::Sleep(n))),
                 ScriptEvent::Exit(n) => res.push(Method::Custom(Custom::Exit(n))),
-                ScriptEvent::Block { repeat, block } => {
+                ScriptEvent::Run { title, wait } => {
+                    if !self.scripts.iter().any(|item| item.title == title) {
+                        return Err(anyhow!("没有找到标题为 {title:?} 的脚本"));
+                    }
+                    res.push(Method::Custom(Custom::Run { title, wait }))
+                }
+                ScriptEvent::Block { repeat, block, args } => {
                     let block = match block {
                         Block::Name(name) => {
-                            let block = self
-                                .blocks
-                                .get(&name)
-                                .ok_or_else(|| anyhow!("没有找到名为 {name:?} 的 block"))?
-                                .to_owned();
-                            if block.iter().any(|a| a.event.block_has(&name)) {
-                                return Err(anyhow!("不能引用自身同名 {name:?} 的 block"));
-                            }
-                            block
+                            let methods = match self.blocks.get(&name) {
+                                Some(BlockConfig::Params { params, methods }) => {
+                                    let methods = expand_block(&name, params, methods, &args)?;
+                                    Arc::new(self.to_methods(methods, blocks, display)?)
+                                }
+                                _ if !args.is_empty() => return Err(anyhow!("block {name:?} 没有声明参数")),
+                                _ => compiled_block(blocks, &name)?,
+                            };
+                            vec![Method::Call { name: Arc::new(name), methods }]
                         }
-                        Block::Block(block) => block,
+                        Block::Block(_) if !args.is_empty() => return Err(anyhow!("只有命名 block 可以传入参数")),
+                        Block::Block(block) => self.to_methods(block, blocks, display)?,
                     };
-                    let block = self.to_methods(block)?;
-                    for _ in 0..repeat {
-                        res.extend(block.iter().cloned());
-                    }
-                    res.pop();
+                    res.push(Method::Loop { repeat, methods: Arc::new(block) });
                 }
             }
             if let Some(n) = method.await_ {
@@ -172,37 +722,186 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 用参数替换脚本块中的 "$参数名" 占位符
+fn expand_block(
+    name: &str,
+    params: &[String],
+    methods: &[Value],
+    args: &HashMap<String, Value>,
+) -> anyhow::Result<Vec<MethodConfig>> {
+    if let Some(param) = params.iter().find(|param| !args.contains_key(*param)) {
+        return Err(anyhow!("block {name:?} 缺少参数 {param:?}"));
+    }
+    if let Some(arg) = args.keys().find(|arg| !params.contains(arg)) {
+        return Err(anyhow!("block {name:?} 没有参数 {arg:?}"));
+    }
+    methods
+        .iter()
+        .map(|method| {
+            let mut method = method.clone();
+            substitute(&mut method, args).map_err(|param| anyhow!("block {name:?} 使用了未声明的参数 {param:?}"))?;
+            serde_json::from_value(method).map_err(|err| anyhow!("block {name:?} 参数类型错误: {err}"))
+        })
+        .collect()
+}
+
+/// 替换占位符, 返回未声明的参数名
+fn substitute(value: &mut Value, args: &HashMap<String, Value>) -> Result<(), String> {
+    match value {
+        Value::String(s) => {
+            if let Some(param) = s.strip_prefix('$') {
+                *value = args.get(param).cloned().ok_or_else(|| param.to_string())?;
+            }
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(|item| substitute(item, args))?,
+        Value::Object(map) => map.values_mut().try_for_each(|item| substitute(item, args))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 从未替换参数的方法中查找引用的命名脚本块
+fn value_block_names(value: &Value) -> Vec<&str> {
+    match value.get("Block").and_then(|block| block.get("block")) {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(methods)) => methods.iter().flat_map(value_block_names).collect(),
+        _ => vec![],
+    }
+}
+
+fn default_move_rate() -> u64 {
+    100
+}
+
+fn left_button() -> Button {
+    Button::Left
+}
+
+/// 从 title 开始沿等待调用查找循环, 返回循环路径; checked 记录已确认没有循环的脚本
+fn wait_cycle<'a>(
+    graph: &HashMap<&'a str, Vec<&'a str>>,
+    title: &'a str,
+    path: &mut Vec<&'a str>,
+    checked: &mut HashSet<&'a str>,
+) -> Option<Vec<&'a str>> {
+    if let Some(index) = path.iter().position(|item| *item == title) {
+        return Some(path[index..].iter().copied().chain([title]).collect());
+    }
+    if checked.contains(title) {
+        return None;
+    }
+    path.push(title);
+    for next in graph.get(title).into_iter().flatten() {
+        if let Some(cycle) = wait_cycle(graph, next, path, checked) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    checked.insert(title);
+    None
+}
+
+fn compiled_block(blocks: &CompiledBlocks, name: &str) -> anyhow::Result<Arc<Vec<Method>>> {
+    blocks
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("没有找到名为 {name:?} 的 block"))
+}
+
+/// 脚本事件
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ScriptEvent {
     /// 鼠标点击
-    Click(Button),
+    Click(
+        #[serde(deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        Button,
+    ),
 
     /// 鼠标松开
-    ClickUp(Button),
+    ClickUp(
+        #[serde(deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        Button,
+    ),
 
     /// 鼠标按下
-    ClickDown(Button),
+    ClickDown(
+        #[serde(deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        Button,
+    ),
 
     /// 点击指定位置
-    ClickOn(Button, f64, f64),
+    ClickOn(
+        #[serde(deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        Button,
+        Coord,
+        Coord,
+    ),
 
     /// 拖拽到指定位置
-    ClickTo(Button, f64, f64, f64, f64),
+    ClickTo(Drag),
 
     /// 触发单按键
-    Key(Key),
+    Key(
+        #[serde(deserialize_with = "deserialize_key")]
+        #[schemars(with = "KeySchema")]
+        Key,
+    ),
 
     /// 键盘松开
-    KeyUp(Key),
+    KeyUp(
+        #[serde(deserialize_with = "deserialize_key")]
+        #[schemars(with = "KeySchema")]
+        Key,
+    ),
 
     /// 键盘按下
-    KeyDown(Key),
+    KeyDown(
+        #[serde(deserialize_with = "deserialize_key")]
+        #[schemars(with = "KeySchema")]
+        Key,
+    ),
+
+    /// 按住按键 ms 毫秒
+    Hold {
+        #[serde(deserialize_with = "deserialize_key")]
+        #[schemars(with = "KeySchema")]
+        key: Key,
+        ms: u64,
+    },
 
     /// 触发多个按键
-    Keys(Vec<Key>),
+    Keys(KeyList),
 
     /// 移动鼠标到指定位置
-    Move(f64, f64),
+    Move(Coord, Coord),
+
+    /// 相对当前位置移动鼠标
+    MoveBy(f64, f64),
+
+    /// 在 duration 毫秒内沿曲线(linear, ease-in-out, bezier)平滑移动鼠标到指定位置
+    MoveTo {
+        x: Coord,
+        y: Coord,
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        curve: Curve,
+        /// 每秒移动事件数(默认使用全局 move_rate)
+        rate: Option<u64>,
+    },
+
+    /// 点击相对当前位置的坐标(button 默认 Left)
+    ClickAt {
+        #[serde(default = "left_button", deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        button: Button,
+        dx: f64,
+        dy: f64,
+    },
 
     /// 滚轮
     Scroll(i64, i64),
@@ -213,19 +912,538 @@ pub enum ScriptEvent {
     /// 退出程序
     Exit(i32),
 
-    /// 脚本块
-    Block { repeat: usize, block: Block },
+    /// 脚本块(args 为带参数脚本块的参数)
+    Block {
+        repeat: usize,
+        block: Block,
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        args: HashMap<String, Value>,
+    },
+
+    /// 调用其他脚本(使用其自身的延迟、循环次数和显示状态), wait 为 true 时等待其结束
+    Run {
+        title: String,
+        #[serde(default)]
+        wait: bool,
+    },
 }
 
 impl ScriptEvent {
-    pub fn block_has(&self, name: &str) -> bool {
-        matches!(self,ScriptEvent::Block { block: Block::Name(n), .. } if n == name)
+    /// 是否可以设置按住时间
+    fn holdable(&self) -> bool {
+        matches!(
+            self,
+            ScriptEvent::Key(_)
+                | ScriptEvent::Keys(_)
+                | ScriptEvent::Click(_)
+                | ScriptEvent::ClickOn(..)
+                | ScriptEvent::ClickAt { .. }
+        )
+    }
+
+    /// 引用的命名脚本块(包括嵌套脚本块中的引用)
+    pub fn block_names(&self) -> Vec<&str> {
+        match self {
+            ScriptEvent::Block { block: Block::Name(name), .. } => vec![name.as_str()],
+            ScriptEvent::Block { block: Block::Block(block), .. } => {
+                block.iter().flat_map(|m| m.event.block_names()).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// 命名脚本块定义
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum BlockConfig {
+    /// 脚本方法
+    Methods(Vec<MethodConfig>),
+    /// 带参数的脚本块, 方法中用 "$参数名" 引用参数
+    Params { params: Vec<String>, methods: Vec<Value> },
+}
+
+impl<'de> Deserialize<'de> for BlockConfig {
+    /// 按输入形状选择格式(列表为脚本方法, 表为带参数的脚本块), 保留脚本方法的错误信息
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Params {
+            params: Vec<String>,
+            methods: Vec<Value>,
+        }
+
+        struct BlockVisitor;
+
+        impl<'de> de::Visitor<'de> for BlockVisitor {
+            type Value = BlockConfig;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("脚本方法列表或 { params, methods }")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<BlockConfig, A::Error> {
+                Vec::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(BlockConfig::Methods)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<BlockConfig, A::Error> {
+                let Params { params, methods } = Params::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(BlockConfig::Params { params, methods })
+            }
+        }
+
+        deserializer.deserialize_any(BlockVisitor)
+    }
+}
+
+impl BlockConfig {
+    /// 引用的命名脚本块
+    fn block_names(&self) -> Vec<&str> {
+        match self {
+            BlockConfig::Methods(methods) => methods.iter().flat_map(|m| m.event.block_names()).collect(),
+            BlockConfig::Params { methods, .. } => methods.iter().flat_map(value_block_names).collect(),
+        }
+    }
+}
+
+/// 多个按键
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum KeyList {
+    /// 依次按下, 按相同顺序松开
+    Keys(
+        #[serde(deserialize_with = "deserialize_keys")]
+        #[schemars(with = "KeysSchema")]
+        Vec<Key>,
+    ),
+    /// 指定松开顺序
+    Ordered {
+        #[serde(deserialize_with = "deserialize_keys")]
+        #[schemars(with = "KeysSchema")]
+        keys: Vec<Key>,
+        #[serde(default)]
+        release_order: ReleaseOrder,
+    },
+}
+
+impl<'de> Deserialize<'de> for KeyList {
+    /// 按输入形状选择格式(字符串或列表为按键, 表为指定松开顺序), 保留按键名称的错误信息
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Ordered {
+            #[serde(deserialize_with = "deserialize_keys")]
+            keys: Vec<Key>,
+            #[serde(default)]
+            release_order: ReleaseOrder,
+        }
+
+        struct KeyListVisitor;
+
+        impl<'de> de::Visitor<'de> for KeyListVisitor {
+            type Value = KeyList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("按键列表、快捷键字符串或 { keys, release_order }")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<KeyList, E> {
+                deserialize_keys(de::value::StrDeserializer::<E>::new(v)).map(KeyList::Keys)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<KeyList, A::Error> {
+                deserialize_keys(de::value::SeqAccessDeserializer::new(seq)).map(KeyList::Keys)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<KeyList, A::Error> {
+                let Ordered { keys, release_order } = Ordered::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(KeyList::Ordered { keys, release_order })
+            }
+        }
+
+        deserializer.deserialize_any(KeyListVisitor)
+    }
+}
+
+/// 多个按键的松开顺序
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseOrder {
+    /// 与按下顺序相同
+    #[default]
+    Same,
+    /// 与按下顺序相反
+    Reverse,
+    /// 同时按下并同时松开
+    Simultaneous,
+}
+
+/// 拖拽
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Drag {
+    /// [按键, 起点 x, 起点 y, 终点 x, 终点 y]
+    Points(
+        #[serde(deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        Button,
+        Coord,
+        Coord,
+        Coord,
+        Coord,
+    ),
+    /// 在 duration 毫秒内沿曲线拖拽
+    Timed {
+        #[serde(deserialize_with = "deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        button: Button,
+        x: Coord,
+        y: Coord,
+        x2: Coord,
+        y2: Coord,
+        #[serde(default)]
+        duration: u64,
+        #[serde(default)]
+        curve: Curve,
+    },
+}
+
+impl<'de> Deserialize<'de> for Drag {
+    /// 按输入形状选择格式(列表为起点和终点, 表为指定时间和曲线), 保留按键和坐标的错误信息
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Points(
+            #[serde(deserialize_with = "deserialize_button")] Button,
+            Coord,
+            Coord,
+            Coord,
+            Coord,
+        );
+
+        #[derive(Deserialize)]
+        struct Timed {
+            #[serde(deserialize_with = "deserialize_button")]
+            button: Button,
+            x: Coord,
+            y: Coord,
+            x2: Coord,
+            y2: Coord,
+            #[serde(default)]
+            duration: u64,
+            #[serde(default)]
+            curve: Curve,
+        }
+
+        struct DragVisitor;
+
+        impl<'de> de::Visitor<'de> for DragVisitor {
+            type Value = Drag;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("[按键, x, y, x2, y2] 或 { button, x, y, x2, y2, duration, curve }")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Drag, A::Error> {
+                let Points(button, x, y, x2, y2) = Points::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+                Ok(Drag::Points(button, x, y, x2, y2))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Drag, A::Error> {
+                let Timed { button, x, y, x2, y2, duration, curve } =
+                    Timed::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(Drag::Timed { button, x, y, x2, y2, duration, curve })
+            }
+        }
+
+        deserializer.deserialize_any(DragVisitor)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 脚本块引用
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum Block {
+    /// 命名脚本块
     Name(String),
+    /// 直接嵌套的脚本方法
     Block(Vec<MethodConfig>),
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(data: &str) -> Config {
+        let data = format!(
+            "delay = 20\nscaling = 1.0\noffset = [0.0, 0.0]\npoint = [0.0, 0.0]\nfont_size = 20.0\n\
+             font_color = [0, 0, 0]\nstart = \"many\"\n{data}"
+        );
+        toml::from_str(&data).unwrap()
+    }
+
+    fn config(blocks: &str) -> Config {
+        parse(&format!("[blocks]\n{blocks}"))
+    }
+
+    fn compile_error(blocks: &str) -> String {
+        match config(blocks).compile_blocks(None) {
+            Ok(_) => panic!("编译成功"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn block_cycle_path() {
+        assert_eq!(
+            compile_error(
+                r#"
+                a = [{ Block = { repeat = 1, block = "b" } }]
+                b = [{ Block = { repeat = 1, block = [{ Block = { repeat = 2, block = "a" } }] } }]
+                "#
+            ),
+            "block 存在循环引用: a → b → a"
+        );
+        assert_eq!(
+            compile_error(r#"a = [{ Block = { repeat = 1, block = "a" } }]"#),
+            "block 存在循环引用: a → a"
+        );
+    }
+
+    #[test]
+    fn block_cycle_through_params() {
+        assert_eq!(
+            compile_error(
+                r#"
+                a = [{ Block = { repeat = 1, block = "p", args = { n = 1 } } }]
+                p = { params = ["n"], methods = [{ Sleep = "$n" }, { Block = { repeat = 1, block = "a" } }] }
+                "#
+            ),
+            "block 存在循环引用: a → p → a"
+        );
+    }
+
+    #[test]
+    fn block_missing() {
+        assert_eq!(
+            compile_error(r#"a = [{ Block = { repeat = 1, block = "b" } }]"#),
+            "没有找到名为 \"b\" 的 block"
+        );
+    }
+
+    #[test]
+    fn block_compiled_once() {
+        let blocks = config(
+            r#"
+            a = [{ Sleep = 100 }]
+            b = [{ Block = { repeat = 1, block = "a" } }, { Block = { repeat = 2, block = "a" } }]
+            "#,
+        )
+        .compile_blocks(None)
+        .unwrap();
+        for method in blocks["b"].iter() {
+            let Method::Loop { methods, .. } = method else {
+                panic!("{method:?}")
+            };
+            let Method::Call { name, methods } = &methods[0] else {
+                panic!("{methods:?}")
+            };
+            assert_eq!(name.as_str(), "a");
+            assert!(Arc::ptr_eq(methods, &blocks["a"]));
+        }
+    }
+
+    fn args(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn substitute_nested_placeholders() {
+        let mut value = json!({ "ClickOn": ["Left", "$x", { "y": "$y" }], "text": "not$x" });
+        substitute(&mut value, &args(json!({ "x": 800, "y": "bottom-10" }))).unwrap();
+        assert_eq!(
+            value,
+            json!({ "ClickOn": ["Left", 800, { "y": "bottom-10" }], "text": "not$x" })
+        );
+        assert_eq!(substitute(&mut json!(["$z"]), &args(json!({}))), Err("z".to_string()));
+    }
+
+    #[test]
+    fn expand_block_params() {
+        let params = ["x".to_string(), "y".to_string()];
+        let methods = [json!({ "ClickOn": ["Left", "$x", "$y"] })];
+        let expanded = expand_block("点击", &params, &methods, &args(json!({ "x": 800, "y": "50%" }))).unwrap();
+        let ScriptEvent::ClickOn(button, x, y) = expanded[0].event else {
+            panic!("{expanded:?}")
+        };
+        assert_eq!(button, Button::Left);
+        assert_eq!(x, Coord::px(800.0));
+        assert_eq!(y, Coord { anchor: 0.5, offset: 0.0 });
+    }
+
+    #[test]
+    fn expand_block_errors() {
+        let params = ["x".to_string(), "y".to_string()];
+        let methods = [json!({ "ClickOn": ["Left", "$x", "$y"] })];
+        let error = |params: &[String], methods: &[Value], value| {
+            expand_block("点击", params, methods, &args(value))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&params, &methods, json!({ "x": 1 })),
+            "block \"点击\" 缺少参数 \"y\""
+        );
+        assert_eq!(
+            error(&params, &methods, json!({ "x": 1, "y": 2, "z": 3 })),
+            "block \"点击\" 没有参数 \"z\""
+        );
+        assert_eq!(
+            error(&params[..1], &methods, json!({ "x": 1 })),
+            "block \"点击\" 使用了未声明的参数 \"y\""
+        );
+        assert!(error(&params, &methods, json!({ "x": "middle", "y": 2 })).starts_with("block \"点击\" 参数类型错误: "));
+    }
+
+    #[test]
+    fn wait_cycle_path() {
+        let graph = HashMap::from([("A", vec!["B"]), ("B", vec!["C", "A"]), ("C", vec![]), ("D", vec!["B"])]);
+        let mut checked = HashSet::new();
+        assert_eq!(wait_cycle(&graph, "C", &mut vec![], &mut checked), None);
+        assert_eq!(
+            wait_cycle(&graph, "D", &mut vec![], &mut checked),
+            Some(vec!["B", "A", "B"])
+        );
+        let graph = HashMap::from([("A", vec!["B"]), ("B", vec![]), ("C", vec!["A", "B"])]);
+        let mut checked = HashSet::new();
+        assert!(graph
+            .keys()
+            .all(|title| wait_cycle(&graph, title, &mut vec![], &mut checked).is_none()));
+    }
+
+    fn event(data: &str) -> Result<ScriptEvent, String> {
+        #[derive(Deserialize)]
+        struct Method {
+            event: ScriptEvent,
+        }
+
+        toml::from_str::<Method>(data)
+            .map(|method| method.event)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn drag_by_shape() {
+        let ScriptEvent::ClickTo(Drag::Points(button, x, ..)) =
+            event(r#"event = { ClickTo = ["Left", 1, 2, "right", 4] }"#).unwrap()
+        else {
+            panic!("需要 Points")
+        };
+        assert_eq!((button, x), (Button::Left, Coord::px(1.0)));
+        let ScriptEvent::ClickTo(Drag::Timed { button, y2, duration, .. }) =
+            event(r#"event = { ClickTo = { button = "Right", x = 1, y = 2, x2 = 3, y2 = "bottom", duration = 300 } }"#)
+                .unwrap()
+        else {
+            panic!("需要 Timed")
+        };
+        assert_eq!(
+            (button, y2, duration),
+            (Button::Right, Coord { anchor: 1.0, offset: 0.0 }, 300)
+        );
+    }
+
+    #[test]
+    fn drag_errors_keep_details() {
+        assert!(event(r#"event = { ClickTo = ["Lfet", 1, 2, 3, 4] }"#)
+            .unwrap_err()
+            .contains("无效的按键 \"Lfet\""));
+        assert!(event(r#"event = { ClickTo = ["Left", 1, 2, 3] }"#)
+            .unwrap_err()
+            .contains("invalid length 4"));
+        assert!(
+            event(r#"event = { ClickTo = { button = "Left", x = 1, y = 2, x2 = 3 } }"#)
+                .unwrap_err()
+                .contains("missing field `y2`")
+        );
+    }
+
+    fn hooks_error(data: &str) -> String {
+        match parse(data).hooks() {
+            Ok(_) => panic!("编译成功"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    const SCRIPTS: &str = r#"
+        [[scripts]]
+        title = "A"
+        repeat = 1
+        trigger = "F1"
+        methods = [{ Run = { title = "B", wait = true } }]
+
+        [[scripts]]
+        title = "B"
+        trigger = "F2"
+    "#;
+
+    #[test]
+    fn hooks_reject_endless_blocks() {
+        assert_eq!(
+            hooks_error(
+                r#"
+                on_start = [{ Block = "a" }]
+                [blocks]
+                a = [{ Block = { repeat = 0, block = [{ Sleep = 1 }] } }]
+                "#
+            ),
+            "钩子中不能使用 repeat = 0 的 block"
+        );
+    }
+
+    #[test]
+    fn hooks_reject_endless_waits() {
+        assert_eq!(
+            hooks_error(&format!(
+                "on_start = [{{ Script = \"A\" }}]\n{SCRIPTS}repeat = 0\nmethods = []"
+            )),
+            "钩子不能等待 repeat = 0 的脚本 \"B\" 结束"
+        );
+        assert_eq!(
+            hooks_error(&format!(
+                "on_exit = [{{ Script = \"A\" }}]\n{SCRIPTS}repeat = 1\n\
+                 methods = [{{ Block = {{ repeat = 0, block = [{{ Sleep = 1 }}] }} }}]"
+            )),
+            "等待的脚本 \"B\": 钩子中不能使用 repeat = 0 的 block"
+        );
+        let config = parse(&format!(
+            "on_start = [{{ Script = \"A\" }}]\n{SCRIPTS}repeat = 1\n\
+             methods = [{{ Block = {{ repeat = 3, block = [{{ Run = {{ title = \"A\", wait = false }} }}] }} }}]"
+        ));
+        assert!(config.hooks().is_ok());
+    }
+
+    fn scripts_error(data: &str) -> String {
+        let context = Context {
+            exiter: tokio::sync::mpsc::unbounded_channel().0,
+            commander: tokio::sync::mpsc::unbounded_channel().0,
+            cursor: Default::default(),
+        };
+        match parse(data).to_scripts(&tokio::sync::mpsc::unbounded_channel().0, &context) {
+            Ok(_) => panic!("编译成功"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn scripts_reject_waits_on_endless_scripts() {
+        assert_eq!(
+            scripts_error(&format!("{SCRIPTS}repeat = 0\nmethods = []")),
+            ": 脚本 \"A\" 等待 repeat = 0 的脚本 \"B\" 结束, 会一直等待"
+        );
+        assert_eq!(
+            scripts_error(&format!(
+                "{SCRIPTS}repeat = 1\nmethods = [{{ Block = {{ repeat = 1, block = [{{ Run = {{ title = \"A\", wait = true }} }}] }} }}]"
+            )),
+            ": 脚本等待结束的调用存在循环: A → B → A"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{broadcast, broadcast::error::RecvError, mpsc::UnboundedSender, oneshot, watch},
+};
+
+use crate::script::Title;
+
+/// 发送给脚本列表的命令
+#[derive(Debug)]
+pub enum Command {
+    /// 外部控制请求
+    Request(Request, oneshot::Sender<Response>),
+    /// 脚本中调用其他脚本, 返回被调用脚本的结束信号
+    Run(String, oneshot::Sender<Option<watch::Receiver<()>>>),
+}
+
+/// 控制请求(每行一个 JSON)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    /// 列出脚本及运行状态
+    List,
+    /// 启动脚本
+    Start { title: String },
+    /// 停止脚本
+    Stop { title: String },
+    /// 切换脚本运行状态(与按键触发相同)
+    Toggle { title: String },
+    /// 停止所有脚本并暂停触发
+    Pause,
+    /// 恢复触发
+    Resume,
+    /// 重新读取配置文件中的脚本
+    Reload,
+    /// 订阅脚本状态变化
+    Subscribe,
+}
+
+/// 控制响应(每行一个 JSON)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Response {
+    /// 执行成功
+    Ok,
+    /// 脚本列表
+    List { paused: bool, scripts: Vec<ScriptState> },
+    /// 脚本状态变化
+    State(ScriptState),
+    /// 没有找到脚本
+    NotFound { title: String },
+    /// 执行失败
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptState {
+    pub title: String,
+    pub running: bool,
+}
+
+impl From<Title> for ScriptState {
+    fn from((title, running): Title) -> Self {
+        Self { title: title.to_string(), running }
+    }
+}
+
+/// 控制接口地址
+#[cfg(unix)]
+pub fn address() -> std::path::PathBuf {
+    std::env::temp_dir().join("ms.sock")
+}
+
+/// 控制接口地址
+#[cfg(windows)]
+pub fn address() -> String {
+    r"\\.\pipe\ms".to_string()
+}
+
+/// 启动本地控制接口(unix 使用 Unix domain socket, windows 使用命名管道)
+#[cfg(unix)]
+pub async fn serve(commander: UnboundedSender<Command>, states: broadcast::Sender<Title>) -> io::Result<()> {
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = address();
+    if UnixStream::connect(&path).await.is_ok() {
+        return Err(io::Error::other("已有实例占用控制接口"));
+    }
+    let _ = std::fs::remove_file(&path);
+
+    let listener = UnixListener::bind(&path)?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(handle(stream, commander.clone(), states.subscribe()));
+    }
+}
+
+/// 启动本地控制接口(unix 使用 Unix domain socket, windows 使用命名管道)
+#[cfg(windows)]
+pub async fn serve(commander: UnboundedSender<Command>, states: broadcast::Sender<Title>) -> io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(address())?;
+    loop {
+        server.connect().await?;
+        let stream = server;
+        server = ServerOptions::new().create(address())?;
+        tokio::spawn(handle(stream, commander.clone(), states.subscribe()));
+    }
+}
+
+/// 连接运行中的实例并发送请求
+#[cfg(unix)]
+pub async fn request(request: &Request) -> io::Result<Response> {
+    let stream = tokio::net::UnixStream::connect(address()).await?;
+    exchange(stream, request).await
+}
+
+/// 连接运行中的实例并发送请求
+#[cfg(windows)]
+pub async fn request(request: &Request) -> io::Result<Response> {
+    use std::time::Duration;
+
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    // 管道实例都被占用时稍等重试
+    const ERROR_PIPE_BUSY: i32 = 231;
+    let stream = loop {
+        match ClientOptions::new().open(address()) {
+            Ok(client) => break client,
+            Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY) => {}
+            Err(err) => return Err(err),
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    exchange(stream, request).await
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite>(stream: S, request: &Request) -> io::Result<Response> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut data = serde_json::to_vec(request)?;
+    data.push(b'\n');
+    writer.write_all(&data).await?;
+    writer.flush().await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "实例没有响应"))?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// 处理单个连接
+async fn handle<S>(stream: S, commander: UnboundedSender<Command>, mut states: broadcast::Receiver<Title>)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Subscribe) => loop {
+                match states.recv().await {
+                    Ok(title) => {
+                        if write(&mut writer, &Response::State(title.into())).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            },
+            Ok(request) => {
+                let (responder, response) = oneshot::channel();
+                let _ = commander.send(Command::Request(request, responder));
+                response
+                    .await
+                    .unwrap_or_else(|_| Response::Error { message: "脚本列表已停止".to_string() })
+            }
+            Err(err) => Response::Error { message: format!("无效请求: {err}") },
+        };
+        if write(&mut writer, &response).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn write<W: AsyncWrite + Unpin>(writer: &mut W, response: &Response) -> io::Result<()> {
+    let mut data = serde_json::to_vec(response)?;
+    data.push(b'\n');
+    writer.write_all(&data).await?;
+    writer.flush().await
+}
@@ -0,0 +1,362 @@
+use std::{borrow::Cow, fmt, ops::Add};
+
+use anyhow::anyhow;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::display;
+
+/// 坐标
+///
+/// - 整数为像素, 0 到 1 之间的小数为屏幕比例
+/// - 字符串可以是比例 "50%" 或锚点 left top center right bottom, 后面可以加像素偏移, 如 "right-100" "50%+20"
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Coord {
+    /// 屏幕比例
+    pub anchor: f64,
+    /// 像素偏移
+    pub offset: f64,
+}
+
+impl Coord {
+    pub fn px(offset: f64) -> Self {
+        Self { anchor: 0.0, offset }
+    }
+
+    /// 不依赖屏幕大小的坐标
+    pub fn is_absolute(&self) -> bool {
+        self.anchor == 0.0
+    }
+
+    /// 根据屏幕大小计算坐标
+    pub fn resolve(&self, size: f64) -> f64 {
+        self.anchor * size + self.offset
+    }
+}
+
+impl Add for Coord {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            anchor: self.anchor + rhs.anchor,
+            offset: self.offset + rhs.offset,
+        }
+    }
+}
+
+impl From<f64> for Coord {
+    fn from(value: f64) -> Self {
+        Self::px(value)
+    }
+}
+
+impl std::str::FromStr for Coord {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.rfind(['+', '-']).filter(|i| *i > 0).unwrap_or(s.len());
+        let (anchor, offset) = s.split_at(split);
+        let offset = match offset.trim() {
+            "" => 0.0,
+            offset => offset
+                .replace(' ', "")
+                .parse()
+                .map_err(|_| anyhow!("无效的坐标偏移 {s:?}"))?,
+        };
+        let anchor = match anchor.trim().to_lowercase().as_str() {
+            "left" | "top" => 0.0,
+            "center" => 0.5,
+            "right" | "bottom" => 1.0,
+            percent if percent.ends_with('%') => {
+                percent[..percent.len() - 1]
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("无效的坐标比例 {s:?}"))?
+                    / 100.0
+            }
+            px => {
+                return Ok(Self::px(
+                    px.parse::<f64>().map_err(|_| anyhow!("无效的坐标 {s:?}"))? + offset,
+                ))
+            }
+        };
+        Ok(Self { anchor, offset })
+    }
+}
+
+impl fmt::Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.anchor * 100.0)?;
+        match self.offset {
+            offset if offset > 0.0 => write!(f, "+{offset}"),
+            offset if offset < 0.0 => write!(f, "{offset}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Serialize for Coord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.is_absolute() {
+            true if self.offset.fract() == 0.0 => serializer.serialize_i64(self.offset as i64),
+            true => serializer.serialize_f64(self.offset),
+            false => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Coord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CoordVisitor;
+
+        impl de::Visitor<'_> for CoordVisitor {
+            type Value = Coord;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("像素、屏幕比例或锚点坐标")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Coord, E> {
+                Ok(Coord::px(v as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Coord, E> {
+                Ok(Coord::px(v as f64))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Coord, E> {
+                match v > 0.0 && v < 1.0 {
+                    true => Ok(Coord { anchor: v, offset: 0.0 }),
+                    false => Ok(Coord::px(v)),
+                }
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Coord, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(CoordVisitor)
+    }
+}
+
+impl JsonSchema for Coord {
+    fn schema_name() -> Cow<'static, str> {
+        "Coord".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "坐标: 整数为像素, 0 到 1 之间的小数为屏幕比例; 字符串可以是 \"50%\" 或锚点 left top center right bottom 加偏移, 如 \"right-100\"",
+            "oneOf": [
+                { "type": "number" },
+                { "type": "string", "pattern": "^\\s*((left|top|center|right|bottom|-?[0-9.]+%)\\s*([+-]\\s*[0-9.]+)?|-?[0-9.]+)\\s*$" }
+            ]
+        })
+    }
+}
+
+/// 缩放比例, 数字或 [x, y](两个方向缩放不同时使用)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Scaling {
+    /// 统一缩放
+    Uniform(f64),
+    /// 分别缩放 x 和 y
+    Axes(f64, f64),
+}
+
+impl Scaling {
+    pub fn x(&self) -> f64 {
+        match self {
+            Scaling::Uniform(scaling) | Scaling::Axes(scaling, _) => *scaling,
+        }
+    }
+
+    pub fn y(&self) -> f64 {
+        match self {
+            Scaling::Uniform(scaling) | Scaling::Axes(_, scaling) => *scaling,
+        }
+    }
+}
+
+/// 运行时根据当前屏幕大小计算的鼠标位置(配置坐标)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub x: Coord,
+    pub y: Coord,
+    pub scaling: Scaling,
+    pub offset: (f64, f64),
+    /// 指定的显示器编号, 依赖屏幕大小的坐标在运行时按该显示器当前的大小、缩放和偏移计算, 否则使用主显示器大小
+    pub display: Option<usize>,
+}
+
+impl Position {
+    pub fn is_absolute(&self) -> bool {
+        self.x.is_absolute() && self.y.is_absolute()
+    }
+
+    /// 转换为实际坐标, 屏幕比例和锚点按当前配置坐标下的屏幕大小计算
+    pub fn resolve(&self) -> (f64, f64) {
+        let (scaling, offset, (width, height)) = match (self.is_absolute(), self.display) {
+            (true, _) => (self.scaling, self.offset, (0.0, 0.0)),
+            (false, Some(id)) => match display::display(id) {
+                Ok(display) => (Scaling::Uniform(display.scale), display.offset(), display.size()),
+                Err(err) => {
+                    println!("{err}");
+                    (self.scaling, self.offset, (0.0, 0.0))
+                }
+            },
+            (false, None) => match rdev::display_size() {
+                Ok((width, height)) => (
+                    self.scaling,
+                    self.offset,
+                    (width as f64 * self.scaling.x(), height as f64 * self.scaling.y()),
+                ),
+                Err(err) => {
+                    println!("获取屏幕大小失败: {err:?}");
+                    (self.scaling, self.offset, (0.0, 0.0))
+                }
+            },
+        };
+        (
+            (self.x.resolve(width) + offset.0) / scaling.x(),
+            (self.y.resolve(height) + offset.1) / scaling.y(),
+        )
+    }
+}
+
+/// 屏幕角落
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// 角落区域的边长(配置坐标像素), 鼠标移到屏幕边缘时会停在边缘的像素上
+    const SIZE: f64 = 5.0;
+
+    /// 角落的正方形区域 [x, y, w, h]
+    pub fn region(self) -> [Coord; 4] {
+        let size = Self::SIZE;
+        let start = Coord::px(0.0);
+        let end = Coord { anchor: 1.0, offset: -size };
+        let (x, y) = match self {
+            Corner::TopLeft => (start, start),
+            Corner::TopRight => (end, start),
+            Corner::BottomLeft => (start, end),
+            Corner::BottomRight => (end, end),
+        };
+        [x, y, Coord::px(size), Coord::px(size)]
+    }
+}
+
+/// 实际坐标下的矩形区域
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
+
+impl Area {
+    /// 按当前屏幕计算左上角和右下角之间的区域
+    pub fn resolve((start, end): (Position, Position)) -> Self {
+        let (left, top) = start.resolve();
+        let (right, bottom) = end.resolve();
+        Self { left, top, right, bottom }
+    }
+
+    /// 是否包含实际坐标(包括边界)
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        (self.left..=self.right).contains(&x) && (self.top..=self.bottom).contains(&y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coord(s: &str) -> Coord {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_anchor_and_offset() {
+        assert_eq!(coord("right-100"), Coord { anchor: 1.0, offset: -100.0 });
+        assert_eq!(coord("50%+20"), Coord { anchor: 0.5, offset: 20.0 });
+        assert_eq!(coord(" Center "), Coord { anchor: 0.5, offset: 0.0 });
+        assert_eq!(coord("bottom - 5"), Coord { anchor: 1.0, offset: -5.0 });
+        assert_eq!(coord("top+0.5"), Coord { anchor: 0.0, offset: 0.5 });
+    }
+
+    #[test]
+    fn parse_pixels() {
+        assert_eq!(coord("-100"), Coord::px(-100.0));
+        assert_eq!(coord("100"), Coord::px(100.0));
+        assert_eq!(coord("100+20"), Coord::px(120.0));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "middle".parse::<Coord>().unwrap_err().to_string(),
+            "无效的坐标 \"middle\""
+        );
+        assert_eq!(
+            "abc%".parse::<Coord>().unwrap_err().to_string(),
+            "无效的坐标比例 \"abc%\""
+        );
+        assert_eq!(
+            "right+x".parse::<Coord>().unwrap_err().to_string(),
+            "无效的坐标偏移 \"right+x\""
+        );
+    }
+
+    #[test]
+    fn deserialize_numbers_and_strings() {
+        let coords: Vec<Coord> = serde_json::from_str(r#"[1920, 0.25, 1.0, "right-100"]"#).unwrap();
+        assert_eq!(
+            coords,
+            [
+                Coord::px(1920.0),
+                Coord { anchor: 0.25, offset: 0.0 },
+                Coord::px(1.0),
+                Coord { anchor: 1.0, offset: -100.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in ["100%-100", "50%+20", "25%"] {
+            assert_eq!(coord(s).to_string(), s);
+        }
+        assert_eq!(serde_json::to_string(&Coord::px(100.0)).unwrap(), "100");
+    }
+
+    #[test]
+    fn corner_region() {
+        let [x, y, w, h] = Corner::BottomRight.region();
+        assert_eq!((x.resolve(1920.0), y.resolve(1080.0)), (1915.0, 1075.0));
+        assert_eq!((w, h), (Coord::px(5.0), Coord::px(5.0)));
+        let [x, y, ..] = Corner::TopRight.region();
+        assert_eq!((x.resolve(1920.0), y.resolve(1080.0)), (1915.0, 0.0));
+    }
+
+    #[test]
+    fn area_contains_edges() {
+        let area = Area { left: 10.0, top: 20.0, right: 30.0, bottom: 40.0 };
+        assert!(area.contains((10.0, 40.0)));
+        assert!(area.contains((20.0, 30.0)));
+        assert!(!area.contains((9.9, 30.0)));
+        assert!(!area.contains((20.0, 40.1)));
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::anyhow;
+use display_info::DisplayInfo;
+
+/// 显示器
+#[derive(Debug, Clone, Copy)]
+pub struct Display {
+    /// 编号(从 1 开始, 主显示器为 1)
+    pub id: usize,
+    /// 相对虚拟桌面左上角的位置
+    pub x: f64,
+    pub y: f64,
+    /// 系统坐标下的大小
+    pub width: f64,
+    pub height: f64,
+    /// 缩放比例
+    pub scale: f64,
+    pub primary: bool,
+}
+
+impl Display {
+    /// 该显示器的配置坐标偏移
+    pub fn offset(&self) -> (f64, f64) {
+        (self.x * self.scale, self.y * self.scale)
+    }
+
+    /// 配置坐标下的屏幕大小
+    pub fn size(&self) -> (f64, f64) {
+        (self.width * self.scale, self.height * self.scale)
+    }
+}
+
+/// 获取所有显示器, 主显示器排在最前, 其余按位置从左到右、从上到下排列
+pub fn displays() -> anyhow::Result<Vec<Display>> {
+    let mut infos = DisplayInfo::all().map_err(|err| anyhow!("获取显示器信息失败: {err}"))?;
+    infos.sort_by_key(|info| (!info.is_primary, info.x, info.y));
+
+    // 模拟的鼠标坐标以虚拟桌面左上角为原点, 主显示器左侧或上方有显示器时原点为负数
+    let left = infos.iter().map(|info| info.x).min().unwrap_or_default();
+    let top = infos.iter().map(|info| info.y).min().unwrap_or_default();
+    Ok(infos
+        .iter()
+        .enumerate()
+        .map(|(index, info)| Display {
+            id: index + 1,
+            x: (info.x - left) as f64,
+            y: (info.y - top) as f64,
+            width: info.width as f64,
+            height: info.height as f64,
+            scale: info.scale_factor as f64,
+            primary: info.is_primary,
+        })
+        .collect())
+}
+
+/// 根据编号获取显示器
+pub fn display(id: usize) -> anyhow::Result<Display> {
+    let displays = displays()?;
+    let count = displays.len();
+    displays
+        .into_iter()
+        .find(|display| display.id == id)
+        .ok_or_else(|| anyhow!("没有编号为 {id} 的显示器(共 {count} 个)"))
+}
+
+/// 系统中的当前鼠标位置(与监听到的鼠标移动事件坐标一致), 获取失败时为空
+#[cfg(target_os = "windows")]
+pub fn cursor() -> Option<(f64, f64)> {
+    use winapi::{shared::windef::POINT, um::winuser::GetPhysicalCursorPos};
+
+    let mut point = POINT { x: 0, y: 0 };
+    // 低级鼠标钩子中的坐标是物理坐标
+    match unsafe { GetPhysicalCursorPos(&mut point) } {
+        0 => None,
+        _ => Some((point.x as f64, point.y as f64)),
+    }
+}
+
+/// 系统中的当前鼠标位置(与监听到的鼠标移动事件坐标一致), 获取失败时为空
+#[cfg(target_os = "linux")]
+pub fn cursor() -> Option<(f64, f64)> {
+    use std::ptr;
+
+    use x11::xlib;
+
+    unsafe {
+        let display = xlib::XOpenDisplay(ptr::null());
+        if display.is_null() {
+            return None;
+        }
+        let (mut root, mut child) = (0, 0);
+        let (mut x, mut y, mut child_x, mut child_y, mut mask) = (0, 0, 0, 0, 0);
+        let found = xlib::XQueryPointer(
+            display,
+            xlib::XDefaultRootWindow(display),
+            &mut root,
+            &mut child,
+            &mut x,
+            &mut y,
+            &mut child_x,
+            &mut child_y,
+            &mut mask,
+        );
+        xlib::XCloseDisplay(display);
+        (found != 0).then_some((x as f64, y as f64))
+    }
+}
+
+/// 其他系统只能使用监听到的鼠标位置
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn cursor() -> Option<(f64, f64)> {
+    None
+}
+
+/// 输出所有显示器信息
+pub fn print() -> anyhow::Result<()> {
+    for display in displays()? {
+        let (width, height) = display.size();
+        println!(
+            "{} {} {}x{} 位置 ({}, {}) 缩放 {}",
+            display.id,
+            if display.primary {
+                "主显示器"
+            } else {
+                "副显示器"
+            },
+            width,
+            height,
+            display.x,
+            display.y,
+            display.scale
+        );
+    }
+    Ok(())
+}
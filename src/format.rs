@@ -0,0 +1,215 @@
+use std::{fs, path::Path};
+
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// 配置文件格式
+///
+/// YAML 和 RON 先转换为 JSON 数据结构再反序列化, 枚举使用字符串或映射表示(与 JSON 相同),
+/// RON 的命名结构体和枚举写法(如 `Click(Left)`)转换时会丢失名称, 读取时报错
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+    Ron,
+}
+
+impl Format {
+    /// 根据文件扩展名选择格式
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        match ext.to_lowercase().as_str() {
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "ron" => Ok(Self::Ron),
+            _ => Err(anyhow!("{}: 不支持的配置文件格式 {ext:?}", path.display())),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, data: &str) -> anyhow::Result<T> {
+        Ok(match self {
+            Self::Toml => toml::from_str(data)?,
+            Self::Json => serde_json::from_str(data)?,
+            Self::Yaml => serde_json::from_value(serde_yaml::from_str::<Value>(data)?)?,
+            Self::Ron => {
+                check_ron(data)?;
+                serde_json::from_value(ron::from_str::<Value>(data)?)?
+            }
+        })
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> anyhow::Result<String> {
+        Ok(match self {
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(&serde_json::to_value(value)?)?,
+            Self::Ron => ron::ser::to_string_pretty(&serde_json::to_value(value)?, Default::default())?,
+        })
+    }
+}
+
+/// 读取配置文件, 错误信息带上文件路径
+pub fn read<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let data = fs::read_to_string(path).map_err(|err| anyhow!("{}: {err}", path.display()))?;
+    Format::from_path(path)?
+        .deserialize(&data)
+        .map_err(|err| anyhow!("{}: {err}", path.display()))
+}
+
+/// 转换配置文件格式, 按原始数据结构转换不会补充默认值或合并 include
+pub fn convert(input: &Path, output: &Path) -> anyhow::Result<()> {
+    let value: Value = read(input)?;
+    let data = Format::from_path(output)?
+        .serialize(&value)
+        .map_err(|err| anyhow!("{}: {err}", output.display()))?;
+    fs::write(output, data).map_err(|err| anyhow!("{}: {err}", output.display()))
+}
+
+/// RON 中除字段名、true false Some None inf NaN 以外的标识符都是命名结构体或枚举, 转换为 JSON 数据结构时会丢失名称
+fn check_ron(data: &str) -> anyhow::Result<()> {
+    let chars: Vec<char> = data.chars().collect();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '\n' => line += 1,
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if next == Some('*') => {
+                let mut depth = 0;
+                while i < chars.len() {
+                    match (chars[i], chars.get(i + 1)) {
+                        ('/', Some('*')) => (depth, i) = (depth + 1, i + 1),
+                        ('*', Some('/')) => (depth, i) = (depth - 1, i + 1),
+                        ('\n', _) => line += 1,
+                        _ => {}
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    match chars[i] {
+                        '\\' => i += 1,
+                        '\n' => line += 1,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+            // 扩展属性, 如 #![enable(implicit_some)]
+            '#' if next == Some('!') => {
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+            }
+            'b' if next == Some('"') => {}
+            'r' if raw_string(&chars, i + 1).is_some() => {
+                let (hashes, start) = raw_string(&chars, i + 1).unwrap();
+                let end: Vec<char> = format!("\"{}", "#".repeat(hashes)).chars().collect();
+                i = start;
+                while i < chars.len() && !chars[i..].starts_with(&end) {
+                    line += usize::from(chars[i] == '\n');
+                    i += 1;
+                }
+                i += end.len();
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '.')) {
+                    i += 1;
+                }
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                if c == 'r' && next == Some('#') {
+                    i += 2;
+                }
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let ident: String = chars[start..i].iter().collect();
+                let after = chars[i..].iter().find(|c| !c.is_whitespace());
+                let allowed = match ident.as_str() {
+                    _ if after == Some(&':') => true,
+                    "Some" => after == Some(&'('),
+                    "true" | "false" | "None" | "inf" | "NaN" => true,
+                    _ => false,
+                };
+                if !allowed {
+                    return Err(anyhow!(
+                        "第 {line} 行: 不支持 RON 的命名结构体或枚举写法 {ident:?}, 枚举请写成字符串或映射, 如 {{\"Click\": \"Left\"}}"
+                    ));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// 原始字符串 r#"..."# 的 # 数量和内容开始位置
+fn raw_string(chars: &[char], start: usize) -> Option<(usize, usize)> {
+    let hashes = chars[start..].iter().take_while(|c| **c == '#').count();
+    (chars.get(start + hashes) == Some(&'"')).then_some((hashes, start + hashes + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ron(data: &str) -> anyhow::Result<Value> {
+        Format::Ron.deserialize(data)
+    }
+
+    #[test]
+    fn ron_accepts_json_structure() {
+        let value = ron(r##"
+            #![enable(implicit_some)]
+            // 注释里的 Click(Left) 不影响
+            /* 块注释 /* 嵌套 */ Left */
+            (
+                delay: 20,
+                scaling: Some(1.5),
+                display: None,
+                methods: [{"Click": "Left"}, {"Text": "Click(Left) 'a'"}, {"Text": r#"Key("A")"#}],
+                move_rate: 1e2,
+                exact: true,
+            )
+        "##)
+        .unwrap();
+        assert_eq!(value["scaling"], 1.5);
+        assert_eq!(value["display"], Value::Null);
+        assert_eq!(value["methods"][0]["Click"], "Left");
+        assert_eq!(value["methods"][1]["Text"], "Click(Left) 'a'");
+        assert_eq!(value["methods"][2]["Text"], "Key(\"A\")");
+    }
+
+    #[test]
+    fn ron_rejects_named_enums() {
+        let err = ron("(\n    methods: [Click(Left)],\n)").unwrap_err().to_string();
+        assert!(
+            err.starts_with("第 2 行: 不支持 RON 的命名结构体或枚举写法 \"Click\""),
+            "{err}"
+        );
+        assert!(ron("(start: Many)").unwrap_err().to_string().contains("\"Many\""));
+        assert!(ron("Config(delay: 20)").is_err());
+    }
+}
@@ -0,0 +1,498 @@
+use anyhow::anyhow;
+use rdev::{Button, EventType, Key};
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+/// 所有按键名称(与 rdev::Key 序列化名称一致, 不包括 Unknown)
+pub const KEYS: [(&str, Key); 105] = [
+    ("Alt", Key::Alt),
+    ("AltGr", Key::AltGr),
+    ("Backspace", Key::Backspace),
+    ("CapsLock", Key::CapsLock),
+    ("ControlLeft", Key::ControlLeft),
+    ("ControlRight", Key::ControlRight),
+    ("Delete", Key::Delete),
+    ("DownArrow", Key::DownArrow),
+    ("End", Key::End),
+    ("Escape", Key::Escape),
+    ("F1", Key::F1),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("Home", Key::Home),
+    ("LeftArrow", Key::LeftArrow),
+    ("MetaLeft", Key::MetaLeft),
+    ("MetaRight", Key::MetaRight),
+    ("PageDown", Key::PageDown),
+    ("PageUp", Key::PageUp),
+    ("Return", Key::Return),
+    ("RightArrow", Key::RightArrow),
+    ("ShiftLeft", Key::ShiftLeft),
+    ("ShiftRight", Key::ShiftRight),
+    ("Space", Key::Space),
+    ("Tab", Key::Tab),
+    ("UpArrow", Key::UpArrow),
+    ("PrintScreen", Key::PrintScreen),
+    ("ScrollLock", Key::ScrollLock),
+    ("Pause", Key::Pause),
+    ("NumLock", Key::NumLock),
+    ("BackQuote", Key::BackQuote),
+    ("Num1", Key::Num1),
+    ("Num2", Key::Num2),
+    ("Num3", Key::Num3),
+    ("Num4", Key::Num4),
+    ("Num5", Key::Num5),
+    ("Num6", Key::Num6),
+    ("Num7", Key::Num7),
+    ("Num8", Key::Num8),
+    ("Num9", Key::Num9),
+    ("Num0", Key::Num0),
+    ("Minus", Key::Minus),
+    ("Equal", Key::Equal),
+    ("KeyQ", Key::KeyQ),
+    ("KeyW", Key::KeyW),
+    ("KeyE", Key::KeyE),
+    ("KeyR", Key::KeyR),
+    ("KeyT", Key::KeyT),
+    ("KeyY", Key::KeyY),
+    ("KeyU", Key::KeyU),
+    ("KeyI", Key::KeyI),
+    ("KeyO", Key::KeyO),
+    ("KeyP", Key::KeyP),
+    ("LeftBracket", Key::LeftBracket),
+    ("RightBracket", Key::RightBracket),
+    ("KeyA", Key::KeyA),
+    ("KeyS", Key::KeyS),
+    ("KeyD", Key::KeyD),
+    ("KeyF", Key::KeyF),
+    ("KeyG", Key::KeyG),
+    ("KeyH", Key::KeyH),
+    ("KeyJ", Key::KeyJ),
+    ("KeyK", Key::KeyK),
+    ("KeyL", Key::KeyL),
+    ("SemiColon", Key::SemiColon),
+    ("Quote", Key::Quote),
+    ("BackSlash", Key::BackSlash),
+    ("IntlBackslash", Key::IntlBackslash),
+    ("KeyZ", Key::KeyZ),
+    ("KeyX", Key::KeyX),
+    ("KeyC", Key::KeyC),
+    ("KeyV", Key::KeyV),
+    ("KeyB", Key::KeyB),
+    ("KeyN", Key::KeyN),
+    ("KeyM", Key::KeyM),
+    ("Comma", Key::Comma),
+    ("Dot", Key::Dot),
+    ("Slash", Key::Slash),
+    ("Insert", Key::Insert),
+    ("KpReturn", Key::KpReturn),
+    ("KpMinus", Key::KpMinus),
+    ("KpPlus", Key::KpPlus),
+    ("KpMultiply", Key::KpMultiply),
+    ("KpDivide", Key::KpDivide),
+    ("Kp0", Key::Kp0),
+    ("Kp1", Key::Kp1),
+    ("Kp2", Key::Kp2),
+    ("Kp3", Key::Kp3),
+    ("Kp4", Key::Kp4),
+    ("Kp5", Key::Kp5),
+    ("Kp6", Key::Kp6),
+    ("Kp7", Key::Kp7),
+    ("Kp8", Key::Kp8),
+    ("Kp9", Key::Kp9),
+    ("KpDelete", Key::KpDelete),
+    ("Function", Key::Function),
+];
+
+/// 修饰键
+pub const MODIFIERS: [Key; 8] = [
+    Key::Alt,
+    Key::AltGr,
+    Key::ControlLeft,
+    Key::ControlRight,
+    Key::MetaLeft,
+    Key::MetaRight,
+    Key::ShiftLeft,
+    Key::ShiftRight,
+];
+
+/// 所有鼠标按键名称(与 rdev::Button 序列化名称一致, 不包括 Unknown)
+pub const BUTTONS: [(&str, Button); 3] = [
+    ("Left", Button::Left),
+    ("Right", Button::Right),
+    ("Middle", Button::Middle),
+];
+
+/// 鼠标侧键名称(rdev 中侧键编号与平台有关)
+#[cfg(target_os = "windows")]
+pub const SIDE_BUTTONS: [(&str, Button); 2] = [("Mouse4", Button::Unknown(1)), ("Mouse5", Button::Unknown(2))];
+
+/// 鼠标侧键名称(rdev 中侧键编号与平台有关)
+#[cfg(not(target_os = "windows"))]
+pub const SIDE_BUTTONS: [(&str, Button); 2] = [("Mouse4", Button::Unknown(8)), ("Mouse5", Button::Unknown(9))];
+
+/// 按键别名
+pub const ALIASES: [(&str, Input); 33] = [
+    ("Ctrl", Input::Modifier(Modifier::Ctrl)),
+    ("Control", Input::Modifier(Modifier::Ctrl)),
+    ("Shift", Input::Modifier(Modifier::Shift)),
+    ("Alt", Input::Modifier(Modifier::Alt)),
+    ("Option", Input::Modifier(Modifier::Alt)),
+    ("Win", Input::Modifier(Modifier::Meta)),
+    ("Meta", Input::Modifier(Modifier::Meta)),
+    ("Super", Input::Modifier(Modifier::Meta)),
+    ("Cmd", Input::Modifier(Modifier::Meta)),
+    ("Command", Input::Modifier(Modifier::Meta)),
+    ("Enter", Input::Key(Key::Return)),
+    ("Esc", Input::Key(Key::Escape)),
+    ("Del", Input::Key(Key::Delete)),
+    ("Ins", Input::Key(Key::Insert)),
+    ("PgUp", Input::Key(Key::PageUp)),
+    ("PgDn", Input::Key(Key::PageDown)),
+    ("Up", Input::Key(Key::UpArrow)),
+    ("Down", Input::Key(Key::DownArrow)),
+    ("Left", Input::Key(Key::LeftArrow)),
+    ("Right", Input::Key(Key::RightArrow)),
+    ("0", Input::Key(Key::Num0)),
+    ("1", Input::Key(Key::Num1)),
+    ("2", Input::Key(Key::Num2)),
+    ("3", Input::Key(Key::Num3)),
+    ("4", Input::Key(Key::Num4)),
+    ("5", Input::Key(Key::Num5)),
+    ("6", Input::Key(Key::Num6)),
+    ("7", Input::Key(Key::Num7)),
+    ("8", Input::Key(Key::Num8)),
+    ("9", Input::Key(Key::Num9)),
+    ("Mouse1", Input::Mouse(Button::Left)),
+    ("Mouse2", Input::Mouse(Button::Right)),
+    ("Mouse3", Input::Mouse(Button::Middle)),
+];
+
+/// 不区分左右的修饰键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Meta,
+}
+
+impl Modifier {
+    /// 左右两侧的按键
+    pub fn keys(self) -> [Key; 2] {
+        match self {
+            Modifier::Ctrl => [Key::ControlLeft, Key::ControlRight],
+            Modifier::Shift => [Key::ShiftLeft, Key::ShiftRight],
+            Modifier::Alt => [Key::Alt, Key::AltGr],
+            Modifier::Meta => [Key::MetaLeft, Key::MetaRight],
+        }
+    }
+
+    /// 按键对应的修饰键
+    pub fn of(key: Key) -> Option<Self> {
+        [Modifier::Ctrl, Modifier::Shift, Modifier::Alt, Modifier::Meta]
+            .into_iter()
+            .find(|modifier| modifier.keys().contains(&key))
+    }
+}
+
+/// 按键名称解析结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Key(Key),
+    /// 不区分左右的修饰键, 模拟时使用左侧按键
+    Modifier(Modifier),
+    Mouse(Button),
+    Wheel(Wheel),
+}
+
+impl Input {
+    /// 转换为键盘按键
+    pub fn key(self) -> anyhow::Result<Key> {
+        match self {
+            Input::Key(key) => Ok(key),
+            Input::Modifier(modifier) => Ok(modifier.keys()[0]),
+            Input::Mouse(button) => Err(anyhow!("需要键盘按键, 不能使用鼠标按键 {button:?}")),
+            Input::Wheel(wheel) => Err(anyhow!("需要键盘按键, 不能使用滚轮 {wheel:?}")),
+        }
+    }
+
+    /// 转换为鼠标按键
+    pub fn button(self) -> anyhow::Result<Button> {
+        match self {
+            Input::Mouse(button) => Ok(button),
+            input => Err(anyhow!("需要鼠标按键, 不能使用 {input:?}")),
+        }
+    }
+
+    /// 按下和松开事件(不区分左右的修饰键使用左侧按键)
+    pub fn events(self) -> anyhow::Result<(EventType, EventType)> {
+        match self {
+            Input::Mouse(button) => Ok((EventType::ButtonPress(button), EventType::ButtonRelease(button))),
+            Input::Wheel(wheel) => Err(anyhow!("滚轮没有按下和松开, 不能使用 {wheel:?}")),
+            input => {
+                let key = input.key()?;
+                Ok((EventType::KeyPress(key), EventType::KeyRelease(key)))
+            }
+        }
+    }
+}
+
+/// 滚轮方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum Wheel {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Wheel {
+    /// 滚轮事件的方向(delta_y 为正向上, delta_x 为正向右)
+    pub fn of(delta_x: i64, delta_y: i64) -> Vec<Self> {
+        let mut wheels = vec![];
+        match delta_y.signum() {
+            1 => wheels.push(Wheel::Up),
+            -1 => wheels.push(Wheel::Down),
+            _ => {}
+        }
+        match delta_x.signum() {
+            1 => wheels.push(Wheel::Right),
+            -1 => wheels.push(Wheel::Left),
+            _ => {}
+        }
+        wheels
+    }
+}
+
+/// 滚轮名称
+pub const WHEELS: [(&str, Wheel); 4] = [
+    ("WheelUp", Wheel::Up),
+    ("WheelDown", Wheel::Down),
+    ("WheelLeft", Wheel::Left),
+    ("WheelRight", Wheel::Right),
+];
+
+/// 所有按键名称: 别名、键盘按键、鼠标按键(MouseLeft Mouse1 等)、侧键和滚轮
+///
+/// 别名优先, "Alt" 与 Ctrl Shift Win 一样不区分左右(rdev 的 Key::Alt 是左 Alt)
+pub fn names() -> Vec<(String, Input)> {
+    let keys = KEYS.iter().map(|(name, key)| (name.to_string(), Input::Key(*key)));
+    let aliases = ALIASES.iter().map(|(name, input)| (name.to_string(), *input));
+    let buttons = BUTTONS
+        .iter()
+        .map(|(name, button)| (format!("Mouse{name}"), Input::Mouse(*button)));
+    let sides = SIDE_BUTTONS
+        .iter()
+        .map(|(name, button)| (name.to_string(), Input::Mouse(*button)));
+    let wheels = WHEELS
+        .iter()
+        .map(|(name, wheel)| (name.to_string(), Input::Wheel(*wheel)));
+    aliases.chain(keys).chain(buttons).chain(sides).chain(wheels).collect()
+}
+
+/// 根据名称获取按键(不区分大小写, 支持别名和鼠标按键), 无效时提示最接近的名称
+pub fn parse_input(name: &str) -> anyhow::Result<Input> {
+    let name = name.trim();
+    // 单个字母为字母键
+    let name = match name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => format!("Key{name}"),
+        false => name.to_string(),
+    };
+    let names = names();
+    if let Some((_, input)) = names.iter().find(|(item, _)| item.eq_ignore_ascii_case(&name)) {
+        return Ok(*input);
+    }
+    let lower = name.to_lowercase();
+    let closest = names
+        .iter()
+        .map(|(item, _)| (distance(&lower, &item.to_lowercase()), item))
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance <= 2.max(name.chars().count() / 3));
+    match closest {
+        Some((_, item)) => Err(anyhow!("无效的按键 {name:?}, 是否是 {item:?}?")),
+        None => Err(anyhow!("无效的按键 {name:?}")),
+    }
+}
+
+/// 根据名称获取键盘按键
+pub fn parse_key(name: &str) -> anyhow::Result<Key> {
+    parse_input(name)?.key()
+}
+
+/// 解析快捷键字符串, 如 "Ctrl+Shift+S" "Ctrl+Mouse4"
+pub fn parse_accelerator(accelerator: &str) -> anyhow::Result<Vec<Input>> {
+    if accelerator.trim().is_empty() {
+        return Err(anyhow!("快捷键不能为空"));
+    }
+    accelerator
+        .split('+')
+        .map(|name| parse_input(name).map_err(|err| anyhow!("快捷键 {accelerator:?}: {err}")))
+        .collect()
+}
+
+/// 编辑距离
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            curr.push((prev[j] + usize::from(ca != *cb)).min(prev[j + 1] + 1).min(curr[j] + 1));
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// 反序列化按键名称(不区分大小写, 支持别名)或 { Unknown = 编号 }
+pub fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+    KeyInput::deserialize(deserializer)?.key().map_err(de::Error::custom)
+}
+
+/// 反序列化多个按键: 按键列表或快捷键字符串
+pub fn deserialize_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Key>, D::Error> {
+    match KeysInput::deserialize(deserializer)? {
+        KeysInput::Accelerator(accelerator) => parse_accelerator(&accelerator)
+            .and_then(|inputs| inputs.into_iter().map(Input::key).collect())
+            .map_err(de::Error::custom),
+        KeysInput::Keys(keys) => keys
+            .into_iter()
+            .map(|key| key.key().map_err(de::Error::custom))
+            .collect(),
+    }
+}
+
+/// 反序列化鼠标按键: 名称(Left Right Middle, 侧键 Mouse4 Mouse5, 不区分大小写)或 { Unknown = 编号 }
+pub fn deserialize_button<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Button, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ButtonInput {
+        Name(String),
+        Button(Button),
+    }
+
+    let button = match ButtonInput::deserialize(deserializer)? {
+        ButtonInput::Name(name) => match BUTTONS.iter().find(|(item, _)| item.eq_ignore_ascii_case(name.trim())) {
+            Some((_, button)) => *button,
+            None => parse_input(&name).and_then(Input::button).map_err(de::Error::custom)?,
+        },
+        ButtonInput::Button(button) => button,
+    };
+    check_button(button).map_err(de::Error::custom)
+}
+
+/// Windows 只有两个侧键
+#[cfg(target_os = "windows")]
+fn check_button(button: Button) -> anyhow::Result<Button> {
+    match button {
+        Button::Unknown(code) if !SIDE_BUTTONS.iter().any(|(_, side)| *side == button) => {
+            Err(anyhow!("无效的鼠标按键 Unknown({code}), 侧键请使用 Mouse4 Mouse5"))
+        }
+        button => Ok(button),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn check_button(button: Button) -> anyhow::Result<Button> {
+    Ok(button)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyInput {
+    Name(String),
+    Key(Key),
+}
+
+impl KeyInput {
+    fn key(self) -> anyhow::Result<Key> {
+        match self {
+            KeyInput::Name(name) => parse_key(&name),
+            KeyInput::Key(key) => Ok(key),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeysInput {
+    Accelerator(String),
+    Keys(Vec<KeyInput>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accelerator_modifiers_are_side_agnostic() {
+        let inputs = parse_accelerator("Ctrl+Alt+Del").unwrap();
+        assert_eq!(
+            inputs,
+            [
+                Input::Modifier(Modifier::Ctrl),
+                Input::Modifier(Modifier::Alt),
+                Input::Key(Key::Delete),
+            ]
+        );
+        assert_eq!(parse_input("AltGr").unwrap(), Input::Key(Key::AltGr));
+    }
+
+    #[test]
+    fn accelerator_ignores_case_and_spaces() {
+        let inputs = parse_accelerator("ctrl + SHIFT + s").unwrap();
+        assert_eq!(
+            inputs,
+            [
+                Input::Modifier(Modifier::Ctrl),
+                Input::Modifier(Modifier::Shift),
+                Input::Key(Key::KeyS),
+            ]
+        );
+        assert_eq!(parse_input("mouse4").unwrap(), Input::Mouse(SIDE_BUTTONS[0].1));
+        assert_eq!(parse_input("MouseLeft").unwrap(), Input::Mouse(Button::Left));
+        assert_eq!(parse_input("wheelup").unwrap(), Input::Wheel(Wheel::Up));
+    }
+
+    #[test]
+    fn accelerator_errors() {
+        assert_eq!(parse_accelerator(" ").unwrap_err().to_string(), "快捷键不能为空");
+        assert_eq!(
+            parse_accelerator("Ctrll+S").unwrap_err().to_string(),
+            "快捷键 \"Ctrll+S\": 无效的按键 \"Ctrll\", 是否是 \"Ctrl\"?"
+        );
+        assert!(parse_accelerator("Ctrl+").is_err());
+        assert_eq!(
+            parse_input("Qwertyuiop").unwrap_err().to_string(),
+            "无效的按键 \"Qwertyuiop\""
+        );
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(distance("ctrl", "ctrl"), 0);
+        assert_eq!(distance("ctrll", "ctrl"), 1);
+        assert_eq!(distance("esacpe", "escape"), 2);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn input_events() {
+        assert_eq!(
+            Input::Modifier(Modifier::Alt).events().unwrap(),
+            (EventType::KeyPress(Key::Alt), EventType::KeyRelease(Key::Alt))
+        );
+        assert!(Input::Wheel(Wheel::Up).events().is_err());
+        assert!(parse_key("Mouse1").is_err());
+    }
+}
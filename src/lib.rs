@@ -2,17 +2,31 @@ use std::{
     ops::Sub,
     path::PathBuf,
     process::exit,
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use anyhow::anyhow;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use rdev::{listen, Event, EventType, Key};
-use tokio::task::spawn_blocking;
+use serde::Serialize;
+use tokio::{sync::mpsc, task::spawn_blocking};
 
-use crate::config::{Config, ScriptEvent};
+use crate::{
+    config::{Config, ScriptEvent},
+    control::{Command, Request, Response},
+    keys::Wheel,
+    script::{listening, Context, Pressed, Trigger},
+};
 
+pub mod calibrate;
 pub mod config;
+pub mod control;
+pub mod coord;
+pub mod display;
+pub mod format;
+pub mod keys;
+pub mod motion;
+pub mod schema;
 pub mod script;
 pub mod start;
 pub mod window;
@@ -28,15 +42,46 @@ pub struct Cli {
 impl Cli {
     pub async fn run(self) {
         match self.sub_command.unwrap_or_default() {
-            Commands::Run { config } => {
-                if let Err(err) = run(config).await {
+            Commands::Run { config } => match run(config).await {
+                Ok(code) => exit(code),
+                Err(err) => {
                     println!("{err}");
                     tokio::time::sleep(Duration::from_secs(60)).await;
                 }
+            },
+            Commands::Event { json, trigger } => {
+                if let Err(err) = event(json, trigger) {
+                    println!("{err}");
+                    exit(1);
+                }
+            }
+            Commands::Point { config, display, key, snippet, list } => {
+                if let Err(err) = point(config, display, key, snippet, list) {
+                    println!("{err}");
+                    exit(1);
+                }
             }
-            Commands::Event => event(),
-            Commands::Point => point(),
             Commands::Record => record(),
+            Commands::Ctl { command } => exit(ctl(command).await),
+            Commands::Calibrate { config } => {
+                if let Err(err) = calibrate::calibrate(&config) {
+                    println!("{err}");
+                    exit(1);
+                }
+            }
+            Commands::Displays => {
+                if let Err(err) = display::print() {
+                    println!("{err}");
+                    exit(1);
+                }
+            }
+            Commands::Schema => println!("{}", schema::schema()),
+            Commands::Convert { input, output } => {
+                if let Err(err) = format::convert(&input, &output) {
+                    println!("{err}");
+                    exit(1);
+                }
+            }
         }
     }
 }
@@ -45,15 +90,106 @@ impl Cli {
 pub enum Commands {
     /// 运行脚本
     Run {
-        /// 配置文件所在路径
+        /// 配置文件所在路径(根据扩展名: toml yaml json ron)
         config: PathBuf,
     },
-    /// 获取事件代码
-    Event,
-    /// 获取鼠标坐标 PS: Alt 输出当前坐标; Escape 清屏
-    Point,
+    /// 获取事件代码(按下、松开、滚轮, 不输出鼠标移动)
+    Event {
+        /// 每行输出一个 JSON
+        #[arg(long)]
+        json: bool,
+        /// 按下组合键后全部松开, 输出对应的 trigger 配置
+        #[arg(long, conflicts_with = "json")]
+        trigger: bool,
+    },
+    /// 获取鼠标坐标 PS: AltGr 输出当前坐标; Escape 清屏(收集坐标时输出脚本块)
+    Point {
+        /// 配置文件, 按其 scaling offset display 转换为配置坐标
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// 坐标所在显示器(覆盖配置文件中的 display)
+        #[arg(long, requires = "config")]
+        display: Option<usize>,
+        /// 获取坐标的按键
+        #[arg(long, default_value = "AltGr")]
+        key: String,
+        /// 输出格式
+        #[arg(long, value_enum, default_value_t = Snippet::Point)]
+        snippet: Snippet,
+        /// 收集坐标到指定名称的脚本块, Escape 输出
+        #[arg(long)]
+        list: Option<String>,
+    },
     /// 录制事件
     Record,
+    /// 列出显示器编号、大小、位置和缩放
+    Displays,
+    /// 校准缩放和偏移并写回配置文件
+    Calibrate {
+        /// 配置文件所在路径
+        #[arg(default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// 输出配置文件的 JSON Schema
+    Schema,
+    /// 转换配置文件格式(根据扩展名: toml yaml json ron)
+    Convert {
+        /// 输入文件
+        input: PathBuf,
+        /// 输出文件
+        output: PathBuf,
+    },
+    /// 控制运行中的实例
+    Ctl {
+        #[command(subcommand)]
+        command: Ctl,
+    },
+}
+
+/// 控制命令, 退出码: 0 成功; 1 执行失败; 2 没有找到脚本; 3 没有运行中的实例
+#[derive(Debug, Subcommand)]
+pub enum Ctl {
+    /// 列出脚本及运行状态
+    List,
+    /// 启动脚本
+    Start { title: String },
+    /// 停止脚本
+    Stop { title: String },
+    /// 切换脚本运行状态
+    Toggle { title: String },
+    /// 停止所有脚本并暂停触发
+    Pause,
+    /// 恢复触发
+    Resume,
+    /// 重新读取配置文件中的脚本
+    Reload,
+    /// 查看运行状态
+    Status {
+        /// 输出 JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// 获取坐标的输出格式
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Snippet {
+    /// x, y
+    Point,
+    /// { Move = [x, y] }
+    Move,
+    /// { ClickOn = ["Left", x, y] }
+    Click,
+}
+
+impl Snippet {
+    fn format(self, (x, y): (f64, f64)) -> String {
+        match self {
+            Snippet::Point => format!("{x}, {y}"),
+            Snippet::Move => format!("{{ Move = [{x}, {y}] }}"),
+            Snippet::Click => format!("{{ ClickOn = [\"Left\", {x}, {y}] }}"),
+        }
+    }
 }
 
 impl Default for Commands {
@@ -62,53 +198,240 @@ impl Default for Commands {
     }
 }
 
+/// 控制运行中的实例, 返回退出码
+async fn ctl(command: Ctl) -> i32 {
+    let request = match &command {
+        Ctl::List | Ctl::Status { .. } => Request::List,
+        Ctl::Start { title } => Request::Start { title: title.clone() },
+        Ctl::Stop { title } => Request::Stop { title: title.clone() },
+        Ctl::Toggle { title } => Request::Toggle { title: title.clone() },
+        Ctl::Pause => Request::Pause,
+        Ctl::Resume => Request::Resume,
+        Ctl::Reload => Request::Reload,
+    };
+
+    let response = match control::request(&request).await {
+        Ok(response) => response,
+        Err(err) => {
+            eprintln!("没有运行中的实例: {err}");
+            return 3;
+        }
+    };
+    match (command, response) {
+        (_, Response::NotFound { title }) => {
+            eprintln!("没有找到脚本: {title}");
+            return 2;
+        }
+        (_, Response::Error { message }) => {
+            eprintln!("{message}");
+            return 1;
+        }
+        (Ctl::Status { json: true }, response) => println!("{}", serde_json::to_string(&response).unwrap()),
+        (Ctl::Status { json: false }, Response::List { paused, scripts }) => {
+            println!("{}", if paused { "已暂停" } else { "监听中" });
+            for item in scripts.iter().filter(|item| item.running) {
+                println!("● {}", item.title);
+            }
+        }
+        (_, Response::List { scripts, .. }) => {
+            for item in scripts {
+                println!("{} {}", if item.running { "●" } else { "○" }, item.title);
+            }
+        }
+        _ => {}
+    }
+    0
+}
+
 /// 获取事件代码
-fn event() {
-    fn callback(event: Event) {
+fn event(json: bool, trigger: bool) -> anyhow::Result<()> {
+    if trigger {
+        return event_trigger();
+    }
+
+    /// JSON 输出
+    #[derive(Serialize)]
+    struct Line<'a> {
+        /// 时间戳(ms)
+        time: u128,
+        event: &'a EventType,
+        name: &'a Option<String>,
+        modifiers: &'a [Key],
+    }
+
+    let start = Instant::now();
+    let mut modifiers = vec![];
+    let callback = move |event: Event| {
         match event.event_type {
-            EventType::KeyRelease(key) => {
-                println!("🖮 -> {key:?}");
-            }
-            EventType::ButtonRelease(button) => {
-                println!("🖰 -> {button:?}");
+            EventType::KeyPress(key) if keys::MODIFIERS.contains(&key) && !modifiers.contains(&key) => {
+                modifiers.push(key)
             }
+            EventType::KeyRelease(key) => modifiers.retain(|item| *item != key),
+            EventType::MouseMove { .. } => return,
             _ => {}
         }
-    }
-    let _ = listen(callback);
+
+        if json {
+            let time = event.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            let line = Line {
+                time,
+                event: &event.event_type,
+                name: &event.name,
+                modifiers: &modifiers,
+            };
+            return println!("{}", serde_json::to_string(&line).unwrap());
+        }
+
+        let action = match event.event_type {
+            EventType::KeyPress(key) => format!("键盘按下 {key:?}"),
+            EventType::KeyRelease(key) => format!("键盘松开 {key:?}"),
+            EventType::ButtonPress(button) => format!("鼠标按下 {button:?}"),
+            EventType::ButtonRelease(button) => format!("鼠标松开 {button:?}"),
+            EventType::Wheel { delta_x, delta_y } => format!("滚轮 [{delta_x}, {delta_y}]"),
+            EventType::MouseMove { .. } => return,
+        };
+        let mut line = format!("{:>9.3}s {action}", start.elapsed().as_secs_f64());
+        if let Some(name) = &event.name {
+            line += &format!(" name={name:?}");
+        }
+        if !modifiers.is_empty() {
+            line += &format!(" 修饰键 {modifiers:?}");
+        }
+        println!("{line}");
+    };
+    listen(callback).map_err(|err| anyhow!("监听异常: {err:?}"))
 }
 
-async fn run(path: PathBuf) -> anyhow::Result<()> {
+/// 获取组合键: 记录按下的按键, 全部松开后输出 trigger 配置
+fn event_trigger() -> anyhow::Result<()> {
+    println!("按下组合键后全部松开, 输出对应的 trigger 配置");
+    let mut chord: Vec<Trigger> = vec![];
+    let mut pressed = Pressed::default();
+    let callback = move |event: Event| {
+        pressed.update(&event.event_type);
+        let (trigger, press) = match event.event_type {
+            EventType::KeyPress(key) => (Trigger::Key(key), true),
+            EventType::KeyRelease(key) => (Trigger::Key(key), false),
+            EventType::ButtonPress(button) => (Trigger::Mouse(button), true),
+            EventType::ButtonRelease(button) => (Trigger::Mouse(button), false),
+            // 滚轮没有松开事件, 与按住的按键组合后直接输出
+            EventType::Wheel { delta_x, delta_y } => {
+                for wheel in Wheel::of(delta_x, delta_y) {
+                    let mut chord = chord.clone();
+                    chord.push(Trigger::Wheel(wheel));
+                    match toml::Value::try_from(&chord) {
+                        Ok(value) => println!("trigger = {value}"),
+                        Err(err) => println!("{err}"),
+                    }
+                }
+                return;
+            }
+            _ => return,
+        };
+        if press {
+            if !chord.contains(&trigger) {
+                chord.push(trigger);
+            }
+        } else if chord.contains(&trigger) && !chord.iter().any(|trigger| pressed.is_held(trigger)) {
+            match toml::Value::try_from(&chord) {
+                Ok(value) => println!("trigger = {value}"),
+                Err(err) => println!("{err}"),
+            }
+            chord.clear();
+        }
+    };
+    listen(callback).map_err(|err| anyhow!("监听异常: {err:?}"))
+}
+
+/// 运行脚本, 返回退出码
+async fn run(path: PathBuf) -> anyhow::Result<i32> {
     let config = Config::parse(path)?;
     let _only_app = config.start.run().map_err(|err| anyhow!("启动失败: {err}"))?;
 
-    let (script, window) = config.load()?;
-    tokio::select! {
-        res = spawn_blocking(move || script.listening()) => {
-            res?.map_err(|err|anyhow!("监听异常: {err:?}"))
+    let (exiter, mut exit_code) = mpsc::unbounded_channel::<i32>();
+    let (commander, commands) = mpsc::unbounded_channel::<Command>();
+    let context = Context {
+        exiter,
+        commander: commander.clone(),
+        cursor: Default::default(),
+    };
+    let hooks = config.hooks()?;
+    let (script, window) = config.load(context.clone())?;
+    let interceptor = script.interceptor.clone();
+    let grab = script.intercepts();
+    let events = script.spawn(commands);
+
+    hooks.start(&context).await;
+
+    let states = window.states.clone();
+    tokio::spawn(async move {
+        if let Err(err) = control::serve(commander, states).await {
+            println!("控制接口异常: {err}");
         }
+    });
+
+    let res = tokio::select! {
+        res = spawn_blocking(move || listening(events, interceptor, grab)) => res?.map(|_| 0),
         res = spawn_blocking(move || window.run()) => {
-            res?.map_err(|err|anyhow!("窗口异常: {err}"))
+            res?.map(|_| 0).map_err(|err|anyhow!("窗口异常: {err}"))
         }
-    }
+        Some(code) = exit_code.recv() => Ok(code),
+        _ = tokio::signal::ctrl_c() => Ok(0),
+    };
+    hooks.exit(&context).await;
+    res
 }
 
-/// 获取坐标
-fn point() {
+/// 获取坐标, 指定配置文件时输出配置坐标
+fn point(
+    config: Option<PathBuf>,
+    display: Option<usize>,
+    key: String,
+    snippet: Snippet,
+    list: Option<String>,
+) -> anyhow::Result<()> {
+    let capture = keys::parse_key(&key)?;
+    let config = config.map(Config::parse).transpose()?;
+    // 脚本块中只能是脚本方法
+    let method = match snippet {
+        Snippet::Point => Snippet::Move,
+        snippet => snippet,
+    };
+
     let mut point = (0.0, 0.0);
+    let mut points = vec![];
     let callback = move |event: Event| match event.event_type {
         EventType::MouseMove { x, y } => {
             point = (x, y);
         }
-        EventType::KeyRelease(Key::AltGr) => {
-            println!("{}, {}", point.0, point.1)
-        }
-        EventType::KeyRelease(Key::Escape) => {
-            println!("\x1B[2J\x1B[1;1H");
+        EventType::KeyRelease(key) if key == capture => {
+            let (x, y) = match &config {
+                Some(config) => match config.inverse(point, display) {
+                    Ok(point) => point,
+                    Err(err) => return println!("{err}"),
+                },
+                None => point,
+            };
+            let point = (x.round(), y.round());
+            println!("{}", snippet.format(point));
+            if list.is_some() {
+                points.push(point);
+            }
         }
+        EventType::KeyRelease(Key::Escape) => match &list {
+            Some(name) if !points.is_empty() => {
+                println!("# [blocks] 中的脚本块");
+                println!("{name:?} = [");
+                for point in points.drain(..) {
+                    println!("    {},", method.format(point));
+                }
+                println!("]");
+            }
+            _ => println!("\x1B[2J\x1B[1;1H"),
+        },
         _ => {}
     };
-    let _ = listen(callback);
+    listen(callback).map_err(|err| anyhow!("监听异常: {err:?}"))
 }
 
 /// 录制事件
@@ -136,11 +459,11 @@ fn record() {
             EventType::KeyPress(key) => res.push(ScriptEvent::KeyDown(key)),
             EventType::KeyRelease(key) => res.push(ScriptEvent::KeyUp(key)),
             EventType::ButtonPress(button) => {
-                res.push(ScriptEvent::Move(point.0, point.1));
+                res.push(ScriptEvent::Move(point.0.into(), point.1.into()));
                 res.push(ScriptEvent::ClickDown(button))
             }
             EventType::ButtonRelease(button) => {
-                res.push(ScriptEvent::Move(point.0, point.1));
+                res.push(ScriptEvent::Move(point.0.into(), point.1.into()));
                 res.push(ScriptEvent::ClickUp(button))
             }
             EventType::Wheel { delta_x, delta_y } => res.push(ScriptEvent::Scroll(delta_x, delta_y)),
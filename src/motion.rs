@@ -0,0 +1,62 @@
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 鼠标平滑移动曲线
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Curve {
+    /// 匀速直线
+    #[default]
+    Linear,
+    /// 先加速后减速的直线
+    EaseInOut,
+    /// 随机控制点的贝塞尔曲线(先加速后减速)
+    Bezier,
+}
+
+impl Curve {
+    /// 生成从 from 到 to 的 steps 个路径点(不含起点, 含终点)
+    pub fn path(self, from: (f64, f64), to: (f64, f64), steps: u64) -> Vec<(f64, f64)> {
+        let steps = steps.max(1);
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let lerp = |t: f64| (from.0 + dx * t, from.1 + dy * t);
+
+        match self {
+            Curve::Linear => (1..=steps).map(|i| lerp(i as f64 / steps as f64)).collect(),
+            Curve::EaseInOut => (1..=steps)
+                .map(|i| lerp(ease_in_out(i as f64 / steps as f64)))
+                .collect(),
+            Curve::Bezier => {
+                // 控制点沿垂直方向随机偏移, 偏移量不超过距离的 30%
+                let mut rng = rand::thread_rng();
+                let mut control = |t: f64| {
+                    let offset = rng.gen_range(-0.3..0.3);
+                    let (x, y) = lerp(t);
+                    (x - dy * offset, y + dx * offset)
+                };
+                let (p1, p2) = (control(0.3), control(0.7));
+                (1..=steps)
+                    .map(|i| cubic_bezier(from, p1, p2, to, ease_in_out(i as f64 / steps as f64)))
+                    .collect()
+            }
+        }
+    }
+}
+
+fn ease_in_out(t: f64) -> f64 {
+    if t < 0.5 {
+        2.0 * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+    }
+}
+
+fn cubic_bezier(p0: (f64, f64), p1: (f64, f64), p2: (f64, f64), p3: (f64, f64), t: f64) -> (f64, f64) {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
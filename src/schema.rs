@@ -0,0 +1,131 @@
+use std::borrow::Cow;
+
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+
+use crate::{
+    config::Config,
+    keys::{self, Input, ALIASES, BUTTONS, KEYS, SIDE_BUTTONS},
+    script::Trigger,
+};
+
+/// 生成配置文件的 JSON Schema
+pub fn schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(Config)).unwrap()
+}
+
+/// rdev::Key 的 Schema
+pub struct KeySchema;
+
+impl JsonSchema for KeySchema {
+    fn schema_name() -> Cow<'static, str> {
+        "Key".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let keys = KEYS.iter().map(|(name, _)| *name);
+        let aliases = ALIASES
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !KEYS.iter().any(|(key, _)| key == name));
+        // 单个字母为字母键
+        let mut accepted = accepted_names(|input| input.key().is_ok());
+        accepted.push("[A-Za-z]".to_string());
+        unknown_schema(
+            "键盘按键(不区分大小写, 单个字母为字母键, 可使用别名)",
+            keys.chain(aliases).collect(),
+            accepted,
+        )
+    }
+}
+
+/// 按键列表或快捷键字符串的 Schema
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub enum KeysSchema {
+    /// 快捷键字符串, 如 "Ctrl+Shift+S"
+    Accelerator(String),
+    /// 按键列表
+    Keys(Vec<KeySchema>),
+}
+
+/// 触发按键列表或快捷键字符串的 Schema
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub enum TriggersSchema {
+    /// 快捷键字符串, 如 "Ctrl+Shift+S", Ctrl Shift Alt Win 不区分左右
+    Accelerator(String),
+    /// 触发按键列表
+    List(Vec<TriggerSchema>),
+    /// 单个触发, 如 { Typed = ";sig" }
+    Single(Trigger),
+}
+
+/// 触发按键或快捷键字符串
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub enum TriggerSchema {
+    /// 快捷键字符串
+    Accelerator(String),
+    /// 触发按键
+    Trigger(Trigger),
+}
+
+/// rdev::Button 的 Schema
+pub struct ButtonSchema;
+
+impl JsonSchema for ButtonSchema {
+    fn schema_name() -> Cow<'static, str> {
+        "Button".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let names: Vec<&str> = BUTTONS
+            .iter()
+            .chain(SIDE_BUTTONS.iter())
+            .map(|(name, _)| *name)
+            .collect();
+        let mut accepted = accepted_names(|input| input.button().is_ok());
+        accepted.extend(BUTTONS.iter().map(|(name, _)| ignore_case(name)));
+        unknown_schema("鼠标按键(不区分大小写, 侧键 Mouse4 Mouse5)", names, accepted)
+    }
+}
+
+/// 按键名称或 { Unknown = 编号 }, names 用于编辑器补全, accepted 为解析时接受的名称(正则)
+fn unknown_schema(description: &str, names: Vec<&str>, accepted: Vec<String>) -> Schema {
+    json_schema!({
+        "description": description,
+        "anyOf": [
+            { "type": "string", "enum": names },
+            { "type": "string", "pattern": format!("^\\s*({})\\s*$", accepted.join("|")) },
+            {
+                "type": "object",
+                "properties": { "Unknown": { "type": "integer", "minimum": 0 } },
+                "required": ["Unknown"],
+                "additionalProperties": false
+            }
+        ]
+    })
+}
+
+/// 解析时接受的名称(不区分大小写的正则)
+fn accepted_names(filter: fn(Input) -> bool) -> Vec<String> {
+    keys::names()
+        .into_iter()
+        .filter(|(_, input)| filter(*input))
+        .map(|(name, _)| ignore_case(&name))
+        .collect()
+}
+
+/// 不区分大小写匹配名称的正则, JSON Schema 的正则不支持 (?i)
+fn ignore_case(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphabetic() {
+            true => format!("[{}{}]", c.to_ascii_uppercase(), c.to_ascii_lowercase()),
+            false if c.is_ascii_alphanumeric() => c.to_string(),
+            false => format!("\\{c}"),
+        })
+        .collect()
+}
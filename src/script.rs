@@ -1,70 +1,293 @@
 use std::{
-    collections::{HashMap, HashSet},
-    process::exit,
-    sync::Arc,
-    time::Duration,
+    collections::{HashSet, VecDeque},
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
-use rdev::{listen, simulate, Button, Event, EventType, Key, ListenError};
-use serde::{Deserialize, Serialize};
+use anyhow::anyhow;
+use rdev::{listen, simulate, Button, Event, EventType, Key};
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use tokio::{
-    sync::{mpsc, mpsc::UnboundedSender},
+    sync::{
+        mpsc,
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot, watch,
+    },
     task::JoinHandle,
 };
 
+use crate::{
+    config::Config,
+    control::{Command, Request, Response, ScriptState},
+    coord::{Area, Coord, Corner, Position},
+    display,
+    keys::{self, Input, Modifier, Wheel},
+    motion::Curve,
+    schema::{ButtonSchema, KeySchema},
+};
+
 pub type Title = (Arc<String>, bool);
 
-pub struct ScriptList(pub Vec<Script>);
+/// 监听键鼠事件并发送给脚本列表, 需要拦截事件时使用 grab(拦截规则在启动时确定是否启用)
+pub fn listening(
+    events: UnboundedSender<Event>,
+    interceptor: Arc<Mutex<Interceptor>>,
+    grab: bool,
+) -> anyhow::Result<()> {
+    if !grab {
+        return listen(move |event| {
+            let _ = events.send(event);
+        })
+        .map_err(|err| anyhow!("监听异常: {err:?}"));
+    }
+    // 在单独的线程中模拟重映射的事件, 不阻塞拦截回调
+    let (remapper, remapped) = std::sync::mpsc::channel::<Vec<EventType>>();
+    thread::spawn(move || {
+        for event_type in remapped.into_iter().flatten() {
+            if let Err(err) = simulate(&event_type) {
+                println!("重映射事件 {event_type:?} 执行失败: {err}");
+            }
+        }
+    });
+
+    rdev::grab(move |event| {
+        let mut guard = interceptor.lock().unwrap();
+        // 原按键被拦截, 脚本只会收到模拟的目标按键事件
+        if let Some(remapped) = guard.remap(&event.event_type) {
+            let _ = remapper.send(remapped);
+            return None;
+        }
+        let intercept = guard.intercept(&event.event_type);
+        drop(guard);
+        let _ = events.send(event.clone());
+        (!intercept).then_some(event)
+    })
+    .map_err(|err| anyhow!("拦截监听异常: {err:?}"))
+}
+
+/// 脚本列表, 负责分发触发事件和控制命令
+pub struct ScriptList {
+    pub scripts: Vec<Script>,
+    /// 配置文件路径(重载配置时使用)
+    pub path: PathBuf,
+    pub updater: UnboundedSender<Title>,
+    pub context: Context,
+    /// 暂停时不响应触发
+    pub paused: bool,
+    /// 监听线程中使用的拦截规则
+    pub interceptor: Arc<Mutex<Interceptor>>,
+}
 
 impl ScriptList {
-    /// 监听脚本的触发
-    pub fn listening(mut self) -> Result<(), ListenError> {
+    /// 启动脚本列表, 分发触发事件和控制命令, 返回事件发送端
+    pub fn spawn(mut self, mut commands: UnboundedReceiver<Command>) -> UnboundedSender<Event> {
         let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
 
         tokio::spawn(async move {
-            let mut keys = HashSet::new();
-            let mut mouses = HashSet::new();
-            for trigger in self.0.iter().flat_map(|f| f.trigger.keys()) {
-                match trigger {
-                    Trigger::Key(key) => keys.insert(*key),
-                    Trigger::Mouse(mouse) => mouses.insert(*mouse),
-                };
-            }
+            let (mut keys, mut mouses) = self.triggers();
+            let mut pressed = Pressed::default();
+            let mut typing = Typing::default();
+            self.update_interceptor();
 
-            while let Some(event) = rx.recv().await {
-                match event.event_type {
-                    EventType::KeyPress(key) => {
-                        if keys.contains(&key) {
-                            let key = Trigger::Key(key);
-                            self.0.iter_mut().for_each(|item| item.down(&key));
+            loop {
+                let deadline = self.deadline();
+                tokio::select! {
+                    Some(event) = rx.recv() => {
+                        pressed.update(&event.event_type);
+                        if typing.update(&event, &pressed) {
+                            self.typed(&mut typing);
                         }
-                    }
-                    EventType::KeyRelease(key) => {
-                        if keys.contains(&key) {
-                            let key = Trigger::Key(key);
-                            self.0.iter_mut().for_each(|item| item.up(&key));
+                        match event.event_type {
+                            EventType::MouseMove { x, y } => {
+                                *self.context.cursor.lock().unwrap() = (x, y);
+                                self.hover((x, y));
+                            }
+                            EventType::KeyPress(key) if keys.contains(&key) => {
+                                Trigger::of_key(key).iter().for_each(|trigger| self.down(trigger, &pressed))
+                            }
+                            EventType::KeyRelease(key) if keys.contains(&key) => {
+                                Trigger::of_key(key).iter().for_each(|trigger| self.up(trigger, &pressed))
+                            }
+                            EventType::ButtonPress(button) if mouses.contains(&button) => {
+                                self.down(&Trigger::Mouse(button), &pressed)
+                            }
+                            EventType::ButtonRelease(button) if mouses.contains(&button) => {
+                                self.up(&Trigger::Mouse(button), &pressed)
+                            }
+                            EventType::Wheel { delta_x, delta_y } => {
+                                for wheel in Wheel::of(delta_x, delta_y) {
+                                    self.down(&Trigger::Wheel(wheel), &pressed);
+                                }
+                            }
+                            _ => {}
                         }
                     }
-                    EventType::ButtonPress(button) => {
-                        if mouses.contains(&button) {
-                            let button = Trigger::Mouse(button);
-                            self.0.iter_mut().for_each(|item| item.down(&button));
-                        }
+                    _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now).into()), if deadline.is_some() => {
+                        self.dwell()
                     }
-                    EventType::ButtonRelease(button) => {
-                        if mouses.contains(&button) {
-                            let button = Trigger::Mouse(button);
-                            self.0.iter_mut().for_each(|item| item.up(&button));
+                    Some(command) = commands.recv() => match command {
+                        Command::Request(request, responder) => {
+                            let response = self.handle(request);
+                            (keys, mouses) = self.triggers();
+                            self.update_interceptor();
+                            let _ = responder.send(response);
                         }
-                    }
-                    _ => {}
+                        Command::Run(title, responder) => {
+                            let _ = responder.send(self.call(&title));
+                        }
+                    },
+                    else => break,
                 }
             }
         });
 
-        listen(move |event| {
-            let _ = tx.send(event);
-        })
+        tx
+    }
+
+    /// 脚本中调用其他脚本, 返回被调用脚本的结束信号
+    fn call(&mut self, title: &str) -> Option<watch::Receiver<()>> {
+        let script = self.scripts.iter_mut().find(|item| *item.title == title)?;
+        script.start();
+        script.done.clone()
+    }
+
+    /// 所有脚本用到的触发按键
+    fn triggers(&self) -> (HashSet<Key>, HashSet<Button>) {
+        let mut keys = HashSet::new();
+        let mut mouses = HashSet::new();
+        for trigger in self.scripts.iter().flat_map(|f| f.trigger.iter()) {
+            match trigger {
+                Trigger::Key(key) => keys.insert(*key),
+                Trigger::Mouse(mouse) => mouses.insert(*mouse),
+                Trigger::Modifier(modifier) => {
+                    keys.extend(modifier.keys());
+                    true
+                }
+                Trigger::Wheel(_) | Trigger::Corner { .. } | Trigger::Region { .. } | Trigger::Typed(_) => false,
+            };
+        }
+        (keys, mouses)
+    }
+
+    /// 是否需要在监听线程中拦截事件
+    pub fn intercepts(&self) -> bool {
+        self.scripts.iter().any(|item| item.consume) || !self.interceptor.lock().unwrap().remaps.is_empty()
+    }
+
+    /// 更新拦截规则, 暂停时不拦截滚轮(重映射一直有效)
+    fn update_interceptor(&self) {
+        let wheels = match self.paused {
+            true => vec![],
+            false => self
+                .scripts
+                .iter()
+                .filter(|item| item.consume)
+                .map(|item| (item.trigger.clone(), item.exact))
+                .collect(),
+        };
+        self.interceptor.lock().unwrap().wheels = wheels;
+    }
+
+    fn down(&mut self, key: &Trigger, pressed: &Pressed) {
+        if !self.paused {
+            self.scripts.iter_mut().for_each(|item| item.down(key, pressed));
+        }
+    }
+
+    fn up(&mut self, key: &Trigger, pressed: &Pressed) {
+        if !self.paused {
+            self.scripts.iter_mut().for_each(|item| item.up(key, pressed));
+        }
+    }
+
+    /// 输入字符, 匹配到缩写后清空输入的文本
+    fn typed(&mut self, typing: &mut Typing) {
+        if !self.paused {
+            let mut matched = false;
+            for item in self.scripts.iter_mut() {
+                matched |= item.typed(typing);
+            }
+            if matched {
+                typing.text.clear();
+            }
+        }
+    }
+
+    /// 鼠标移动, 更新停留区域并运行停留时间为 0 的脚本
+    fn hover(&mut self, point: (f64, f64)) {
+        if !self.paused {
+            let now = Instant::now();
+            self.scripts.iter_mut().for_each(|item| item.hover(point, now));
+            self.dwell();
+        }
+    }
+
+    /// 运行停留时间已到的脚本
+    fn dwell(&mut self) {
+        let now = Instant::now();
+        self.scripts.iter_mut().for_each(|item| item.dwell(now));
+    }
+
+    /// 最近的停留到期时间
+    fn deadline(&self) -> Option<Instant> {
+        self.scripts.iter().filter_map(Script::deadline).min()
+    }
+
+    /// 处理控制命令
+    fn handle(&mut self, request: Request) -> Response {
+        let (title, action): (String, fn(&mut Script)) = match request {
+            Request::List => {
+                let scripts = self
+                    .scripts
+                    .iter()
+                    .map(|item| ScriptState { title: item.title.to_string(), running: item.is_running() })
+                    .collect();
+                return Response::List { paused: self.paused, scripts };
+            }
+            Request::Start { title } => (title, Script::start),
+            Request::Stop { title } => (title, Script::stop),
+            Request::Toggle { title } => (title, Script::run),
+            Request::Pause => {
+                self.paused = true;
+                self.scripts.iter_mut().for_each(Script::stop);
+                return Response::Ok;
+            }
+            Request::Resume => {
+                self.paused = false;
+                return Response::Ok;
+            }
+            Request::Reload => {
+                return match self.reload() {
+                    Ok(_) => Response::Ok,
+                    Err(err) => Response::Error { message: format!("重载配置失败: {err}") },
+                }
+            }
+            Request::Subscribe => {
+                return Response::Error {
+                    message: "订阅请求不能转发给脚本列表".to_string()
+                }
+            }
+        };
+
+        match self.scripts.iter_mut().find(|item| *item.title == title) {
+            Some(script) => {
+                action(script);
+                Response::Ok
+            }
+            None => Response::NotFound { title },
+        }
+    }
+
+    /// 重新读取配置文件并替换脚本列表
+    fn reload(&mut self) -> anyhow::Result<()> {
+        let scripts = Config::parse(&self.path)?.to_scripts(&self.updater, &self.context)?;
+        self.scripts.iter_mut().for_each(Script::stop);
+        self.scripts = scripts;
+        Ok(())
     }
 }
 
@@ -75,83 +298,675 @@ pub struct Script {
     pub repeat: usize,
     pub methods: Arc<Vec<Method>>,
     pub task: Option<JoinHandle<()>>,
-    pub trigger: HashMap<Trigger, bool>,
+    pub trigger: Vec<Trigger>,
+    /// 精确匹配修饰键: 按住触发按键以外的修饰键时不触发
+    pub exact: bool,
+    /// 按下还是松开时触发
+    pub on: TriggerOn,
+    /// 拦截触发脚本的滚轮事件
+    pub consume: bool,
+    /// 松开时触发的脚本所有触发按键都已按住过
+    pub armed: bool,
+    /// 鼠标停留触发(Corner Region)
+    pub hover: Option<Hover>,
     pub updater: UnboundedSender<Title>,
+    pub context: Context,
+    /// 脚本结束信号(任务结束或中止时发送端被释放)
+    pub done: Option<watch::Receiver<()>>,
+}
+
+/// 脚本运行时共享的上下文
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// 发送退出码, 由主程序执行退出钩子后退出
+    pub exiter: UnboundedSender<i32>,
+    /// 发送命令给脚本列表
+    pub commander: UnboundedSender<Command>,
+    /// 当前鼠标位置(由监听到的和模拟的鼠标移动事件更新)
+    pub cursor: Arc<Mutex<(f64, f64)>>,
+}
+
+impl Context {
+    /// 获取并记录当前鼠标位置, 系统不支持获取时使用监听到的位置
+    pub fn locate(&self) -> (f64, f64) {
+        let mut cursor = self.cursor.lock().unwrap();
+        if let Some(position) = display::cursor() {
+            *cursor = position;
+        }
+        *cursor
+    }
 }
 
 impl Script {
     pub fn run(&mut self) {
+        self.run_erasing(0)
+    }
+
+    /// 运行脚本, 先删除 erase 个字符(输入缩写触发时); 脚本运行中时停止脚本, 输入的缩写同样会被删除
+    fn run_erasing(&mut self, erase: usize) {
         let title = self.title.clone();
         let updater = self.updater.clone();
+        let delay = self.delay;
+        let context = self.context.clone();
+        let erase: Vec<Method> = (0..erase).flat_map(|_| Method::key(Key::Backspace, None)).collect();
 
         if let Some(task) = self.task.take() {
             if self.repeat == 0 || !task.is_finished() {
                 task.abort();
+                if !erase.is_empty() {
+                    tokio::spawn(async move { run_method(&erase, delay, &context).await });
+                }
                 return updater.send((title.clone(), false)).unwrap();
             }
         }
 
         let _ = updater.send((title.clone(), true));
 
-        let delay = self.delay;
         let repeat = self.repeat;
         let methods = self.methods.clone();
+        let (done, receiver) = watch::channel(());
 
         let task = tokio::task::spawn(async move {
+            let _done = done;
+            if !erase.is_empty() {
+                run_method(&erase, delay, &context).await;
+            }
             if repeat == 0 {
-                loop {
-                    run_method(&methods, delay).await;
-                }
+                while run_method(&methods, delay, &context).await {}
             } else {
                 for _ in 0..repeat {
-                    run_method(&methods, delay).await;
+                    if !run_method(&methods, delay, &context).await {
+                        break;
+                    }
                 }
-                let _ = updater.send((title, false));
             }
+            let _ = updater.send((title, false));
         });
 
         self.task = Some(task);
+        self.done = Some(receiver);
+    }
+
+    /// 启动脚本, 已在运行时不做处理
+    pub fn start(&mut self) {
+        if !self.is_running() {
+            self.task = None;
+            self.run();
+        }
+    }
+
+    /// 停止脚本(鼠标停留触发需要离开区域后才会再次触发)
+    pub fn stop(&mut self) {
+        self.armed = false;
+        if let Some(hover) = &mut self.hover {
+            hover.entered = None;
+        }
+        if let Some(task) = self.task.take() {
+            if !task.is_finished() {
+                task.abort();
+                let _ = self.updater.send((self.title.clone(), false));
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| !task.is_finished())
+    }
+
+    /// 按下触发按键, 所有触发按键都按住时运行(松开时触发的脚本等待全部松开)
+    pub fn down(&mut self, key: &Trigger, pressed: &Pressed) {
+        if pressed.matches(&self.trigger, key, self.exact) {
+            match self.on {
+                // 只有滚轮时没有需要等待松开的按键
+                TriggerOn::Release if self.trigger.iter().any(|trigger| pressed.is_held(trigger)) => self.armed = true,
+                _ => self.run(),
+            }
+        }
+    }
+
+    /// 松开触发按键, 松开时触发的脚本在所有触发按键都松开后运行
+    pub fn up(&mut self, key: &Trigger, pressed: &Pressed) {
+        if self.armed && self.trigger.contains(key) && !self.trigger.iter().any(|trigger| pressed.is_held(trigger)) {
+            self.armed = false;
+            self.run()
+        }
+    }
+
+    /// 输入的文本以缩写结尾时删除缩写并运行, 返回是否匹配
+    pub fn typed(&mut self, typing: &Typing) -> bool {
+        match self.trigger.as_slice() {
+            [Trigger::Typed(typed)] if typing.ends_with(typed) => {
+                self.run_erasing(typed.text().chars().count());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 鼠标移动, 进入区域时开始计时, 离开时重置
+    pub fn hover(&mut self, point: (f64, f64), now: Instant) {
+        if let Some(hover) = &mut self.hover {
+            let inside = hover.area(now).contains(point);
+            if inside != hover.inside {
+                hover.inside = inside;
+                hover.entered = inside.then_some(now);
+            }
+        }
+    }
+
+    /// 停留到期时运行, 离开区域前不再触发
+    pub fn dwell(&mut self, now: Instant) {
+        if self.deadline().is_some_and(|deadline| deadline <= now) {
+            self.hover.as_mut().unwrap().entered = None;
+            self.run();
+        }
+    }
+
+    /// 停留到期时间
+    pub fn deadline(&self) -> Option<Instant> {
+        let hover = self.hover.as_ref()?;
+        hover.entered.map(|entered| entered + hover.dwell)
+    }
+}
+
+/// 鼠标停留触发的状态
+#[derive(Debug, Clone, Copy)]
+pub struct Hover {
+    /// 区域左上角和右下角
+    pub corners: (Position, Position),
+    /// 实际坐标下的区域和计算时间, 屏幕大小可能改变, 定期重新计算
+    area: Option<(Area, Instant)>,
+    pub dwell: Duration,
+    /// 鼠标是否在区域内
+    pub inside: bool,
+    /// 进入区域的时间, 触发后为空
+    pub entered: Option<Instant>,
+}
+
+impl Hover {
+    /// 区域重新计算的间隔
+    const REFRESH: Duration = Duration::from_secs(1);
+
+    pub fn new(corners: (Position, Position), dwell: Duration) -> Self {
+        Self { corners, area: None, dwell, inside: false, entered: None }
+    }
+
+    /// 实际坐标下的区域
+    fn area(&mut self, now: Instant) -> Area {
+        match self.area {
+            Some((area, time)) if now.duration_since(time) < Self::REFRESH => area,
+            _ => {
+                let area = Area::resolve(self.corners);
+                self.area = Some((area, now));
+                area
+            }
+        }
+    }
+}
+
+/// 触发时机
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerOn {
+    /// 所有触发按键按住时触发
+    #[default]
+    Press,
+    /// 所有触发按键按住后全部松开时触发, 避免按住的按键影响脚本模拟的事件
+    Release,
+}
+
+/// 按键重映射
+#[derive(Debug, Clone)]
+pub struct Remap {
+    /// 原按键的按下和松开事件
+    pub from: (EventType, EventType),
+    /// 目标按键的按下和松开事件, 按顺序按下, 反序松开
+    pub to: Vec<(EventType, EventType)>,
+}
+
+impl Remap {
+    /// 原按键事件对应的目标按键事件(按住时的自动重复也会转发)
+    fn events(&self, event_type: &EventType) -> Option<Vec<EventType>> {
+        if *event_type == self.from.0 {
+            Some(self.to.iter().map(|(down, _)| *down).collect())
+        } else if *event_type == self.from.1 {
+            Some(self.to.iter().rev().map(|(_, up)| *up).collect())
+        } else {
+            None
+        }
     }
+}
 
-    pub fn down(&mut self, key: &Trigger) {
-        if let Some(k) = self.trigger.get_mut(key) {
-            *k = true;
+/// 在监听线程中同步判断是否拦截事件
+#[derive(Debug, Default)]
+pub struct Interceptor {
+    pressed: Pressed,
+    /// 拦截滚轮的脚本触发按键和是否精确匹配修饰键
+    wheels: Vec<(Vec<Trigger>, bool)>,
+    /// 按键重映射
+    remaps: Vec<Remap>,
+    /// 已发送等待模拟的重映射事件, 再次监听到时不处理
+    pending: VecDeque<(EventType, Instant)>,
+}
+
+impl Interceptor {
+    /// 模拟的事件没有被监听到(如 linux 拦截时)时的过期时间
+    const PENDING: Duration = Duration::from_secs(1);
 
-            if self.trigger.values().all(|flag| *flag) {
-                self.run()
+    pub fn new(remaps: Vec<Remap>) -> Self {
+        Self { remaps, ..Default::default() }
+    }
+
+    /// 重映射原按键, 返回需要模拟的目标按键事件
+    pub fn remap(&mut self, event_type: &EventType) -> Option<Vec<EventType>> {
+        if self.remaps.is_empty() {
+            return None;
+        }
+        let now = Instant::now();
+        self.pending
+            .retain(|(_, time)| now.duration_since(*time) < Self::PENDING);
+        if let Some(index) = self.pending.iter().position(|(pending, _)| pending == event_type) {
+            self.pending.remove(index);
+            return None;
+        }
+        let events = self.remaps.iter().find_map(|remap| remap.events(event_type))?;
+        self.pending.extend(events.iter().map(|event_type| (*event_type, now)));
+        Some(events)
+    }
+
+    /// 更新按住的按键, 返回是否拦截事件
+    pub fn intercept(&mut self, event_type: &EventType) -> bool {
+        self.pressed.update(event_type);
+        match event_type {
+            EventType::Wheel { delta_x, delta_y } => Wheel::of(*delta_x, *delta_y).into_iter().any(|wheel| {
+                let key = Trigger::Wheel(wheel);
+                self.wheels
+                    .iter()
+                    .any(|(trigger, exact)| self.pressed.matches(trigger, &key, *exact))
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// 当前按住的按键(所有脚本共享)
+#[derive(Debug, Default)]
+pub struct Pressed {
+    pub keys: HashSet<Key>,
+    pub buttons: HashSet<Button>,
+}
+
+impl Pressed {
+    /// 根据事件更新按住的按键
+    pub fn update(&mut self, event_type: &EventType) {
+        match event_type {
+            EventType::KeyPress(key) => {
+                self.keys.insert(*key);
+            }
+            EventType::KeyRelease(key) => {
+                self.keys.remove(key);
+            }
+            EventType::ButtonPress(button) => {
+                self.buttons.insert(*button);
+            }
+            EventType::ButtonRelease(button) => {
+                self.buttons.remove(button);
+            }
+            _ => {}
+        }
+    }
+
+    /// 滚轮没有按住状态
+    pub fn is_held(&self, trigger: &Trigger) -> bool {
+        match trigger {
+            Trigger::Key(key) => self.keys.contains(key),
+            Trigger::Mouse(button) => self.buttons.contains(button),
+            Trigger::Modifier(modifier) => modifier.keys().iter().any(|key| self.keys.contains(key)),
+            Trigger::Wheel(_) | Trigger::Corner { .. } | Trigger::Region { .. } | Trigger::Typed(_) => false,
+        }
+    }
+
+    /// key 按下(或滚动)时触发按键组合是否满足, exact 时不能按住组合以外的修饰键
+    pub fn matches(&self, trigger: &[Trigger], key: &Trigger, exact: bool) -> bool {
+        let has_modifier = |modifier: Key| {
+            trigger.iter().any(|trigger| match trigger {
+                Trigger::Key(key) => *key == modifier,
+                Trigger::Modifier(item) => item.keys().contains(&modifier),
+                _ => false,
+            })
+        };
+        trigger.contains(key)
+            && trigger.iter().all(|trigger| trigger == key || self.is_held(trigger))
+            && !(exact && self.modifiers().any(|modifier| !has_modifier(modifier)))
+    }
+
+    /// 按住的修饰键
+    pub fn modifiers(&self) -> impl Iterator<Item = Key> + '_ {
+        keys::MODIFIERS.into_iter().filter(|key| self.keys.contains(key))
+    }
+}
+
+/// 最近输入的文本, 用于匹配缩写
+#[derive(Debug, Default)]
+pub struct Typing {
+    pub text: String,
+}
+
+impl Typing {
+    /// 最多保留的字符数
+    const CAPACITY: usize = 64;
+
+    /// 根据事件更新输入的文本, 返回是否输入了字符
+    ///
+    /// 点击鼠标、按下 Ctrl Alt Win 组合键(可能切换窗口或粘贴)、回车、Tab 和方向键等不输入字符的按键会清空文本
+    pub fn update(&mut self, event: &Event, pressed: &Pressed) -> bool {
+        match event.event_type {
+            EventType::KeyPress(Key::Backspace) => {
+                self.text.pop();
+                false
+            }
+            EventType::KeyPress(key) if keys::MODIFIERS.contains(&key) => false,
+            EventType::KeyPress(Key::CapsLock | Key::NumLock) => false,
+            EventType::KeyPress(_)
+                if pressed
+                    .modifiers()
+                    .any(|key| !matches!(key, Key::ShiftLeft | Key::ShiftRight)) =>
+            {
+                self.text.clear();
+                false
+            }
+            EventType::KeyPress(_) => match event.name.as_deref().filter(|name| !name.chars().any(char::is_control)) {
+                Some(name) if !name.is_empty() => {
+                    self.text.push_str(name);
+                    let count = self.text.chars().count();
+                    if count > Self::CAPACITY {
+                        self.text = self.text.chars().skip(count - Self::CAPACITY).collect();
+                    }
+                    true
+                }
+                _ => {
+                    self.text.clear();
+                    false
+                }
+            },
+            EventType::ButtonPress(_) => {
+                self.text.clear();
+                false
             }
+            _ => false,
         }
     }
 
-    pub fn up(&mut self, key: &Trigger) {
-        if let Some(k) = self.trigger.get_mut(key) {
-            *k = false;
+    /// 是否以缩写结尾, word 时缩写前面不能是字母或数字
+    pub fn ends_with(&self, typed: &Typed) -> bool {
+        let (text, abbreviation) = match typed.ignore_case() {
+            true => (self.text.to_lowercase(), typed.text().to_lowercase()),
+            false => (self.text.clone(), typed.text().to_string()),
+        };
+        match text.strip_suffix(&abbreviation) {
+            Some(prefix) => !typed.word() || !prefix.chars().last().is_some_and(char::is_alphanumeric),
+            None => false,
         }
     }
 }
 
-/// 运行脚本方法
-async fn run_method(methods: &Arc<Vec<Method>>, delay: u64) {
+/// 模拟鼠标移动并记录位置
+fn mouse_move(x: f64, y: f64, context: &Context) {
+    let event_type = EventType::MouseMove { x, y };
+    if let Err(err) = simulate(&event_type) {
+        println!("事件 {event_type:?} 执行失败: {err}");
+    }
+    *context.cursor.lock().unwrap() = (x, y);
+}
+
+/// 启动和退出时执行的钩子
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    pub on_start: Vec<Hook>,
+    pub on_exit: Vec<Hook>,
+}
+
+impl Hooks {
+    /// 执行启动钩子
+    pub async fn start(&self, context: &Context) {
+        run_hooks(&self.on_start, context).await
+    }
+
+    /// 执行退出钩子
+    pub async fn exit(&self, context: &Context) {
+        run_hooks(&self.on_exit, context).await
+    }
+}
+
+async fn run_hooks(hooks: &[Hook], context: &Context) {
+    for hook in hooks {
+        if !hook.run(context).await {
+            break;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub delay: u64,
+    pub repeat: usize,
+    pub methods: Arc<Vec<Method>>,
+}
+
+impl Hook {
+    /// 执行钩子, 遇到 Exit 时返回 false
+    pub async fn run(&self, context: &Context) -> bool {
+        for _ in 0..self.repeat {
+            if !run_method(&self.methods, self.delay, context).await {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 运行脚本方法, 遇到 Exit 时返回 false
+async fn run_method(methods: &[Method], delay: u64, context: &Context) -> bool {
     for method in methods.iter() {
         match method {
             Method::Event(event_type) => {
                 if let Err(err) = simulate(event_type) {
                     println!("事件 {event_type:?} 执行失败: {err}");
                 }
-                if let EventType::MouseMove { .. } = event_type {
+                if let EventType::MouseMove { x, y } = event_type {
+                    *context.cursor.lock().unwrap() = (*x, *y);
                     tokio::time::sleep(Duration::from_micros(100)).await;
                 } else {
                     tokio::time::sleep(Duration::from_millis(delay)).await;
                 };
             }
-            Method::Custom(c) => c.run().await,
+            Method::Press { events, hold } => {
+                for event_type in events {
+                    if let Err(err) = simulate(event_type) {
+                        println!("事件 {event_type:?} 执行失败: {err}");
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(hold.unwrap_or(delay))).await;
+            }
+            Method::Custom(c) => {
+                if !c.run(context).await {
+                    return false;
+                }
+            }
+            Method::Loop { repeat, methods } => {
+                let mut n = 0;
+                while *repeat == 0 || n < *repeat {
+                    if !Box::pin(run_method(methods, delay, context)).await {
+                        return false;
+                    }
+                    // 保证空循环也能让出执行权, 以便被中止
+                    tokio::task::yield_now().await;
+                    n += 1;
+                }
+            }
+            Method::Call { methods, .. } => {
+                if !Box::pin(run_method(methods, delay, context)).await {
+                    return false;
+                }
+            }
         }
     }
+    true
 }
-#[derive(Debug, Clone, PartialEq, Hash, Eq, Serialize, Deserialize)]
+
+/// 触发按键
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Trigger {
-    Key(Key),
-    Mouse(Button),
+    /// 键盘按键
+    Key(
+        #[serde(deserialize_with = "keys::deserialize_key")]
+        #[schemars(with = "KeySchema")]
+        Key,
+    ),
+    /// 鼠标按键(侧键 Mouse4 Mouse5)
+    Mouse(
+        #[serde(deserialize_with = "keys::deserialize_button")]
+        #[schemars(with = "ButtonSchema")]
+        Button,
+    ),
+    /// 不区分左右的修饰键
+    Modifier(Modifier),
+    /// 滚轮(Up Down Left Right), 没有按住状态, 滚动时与按住的其他触发按键组合
+    Wheel(Wheel),
+    /// 鼠标在屏幕角落停留 dwell_ms 毫秒(不能与其他触发组合)
+    Corner {
+        which: Corner,
+        #[serde(default)]
+        dwell_ms: u64,
+    },
+    /// 鼠标进入区域并停留 dwell_ms 毫秒(配置坐标, 依赖屏幕大小的坐标按当前屏幕计算; 不能与其他触发组合)
+    Region {
+        x: Coord,
+        y: Coord,
+        w: Coord,
+        h: Coord,
+        #[serde(default)]
+        dwell_ms: u64,
+    },
+    /// 输入缩写(如 ";sig")时删除缩写并运行脚本(不能与其他触发组合)
+    Typed(Typed),
+}
+
+/// 输入缩写, 文本或带选项的表
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Typed {
+    /// 区分大小写, 在任意位置匹配
+    Text(String),
+    Options {
+        text: String,
+        /// 只匹配单词开头(缩写前面不是字母或数字)
+        #[serde(default)]
+        word: bool,
+        /// 不区分大小写
+        #[serde(default)]
+        ignore_case: bool,
+    },
+}
+
+impl Typed {
+    pub fn text(&self) -> &str {
+        match self {
+            Typed::Text(text) | Typed::Options { text, .. } => text,
+        }
+    }
+
+    pub fn word(&self) -> bool {
+        matches!(self, Typed::Options { word: true, .. })
+    }
+
+    pub fn ignore_case(&self) -> bool {
+        matches!(self, Typed::Options { ignore_case: true, .. })
+    }
+}
+
+impl Trigger {
+    /// 解析快捷键字符串, 如 "Ctrl+Shift+S", 不区分左右的修饰键匹配任意一侧
+    pub fn parse(accelerator: &str) -> anyhow::Result<Vec<Self>> {
+        let inputs = keys::parse_accelerator(accelerator)?;
+        Ok(inputs
+            .into_iter()
+            .map(|input| match input {
+                Input::Key(key) => Trigger::Key(key),
+                Input::Modifier(modifier) => Trigger::Modifier(modifier),
+                Input::Mouse(button) => Trigger::Mouse(button),
+                Input::Wheel(wheel) => Trigger::Wheel(wheel),
+            })
+            .collect())
+    }
+
+    /// 鼠标停留或输入缩写触发, 不能与其他触发组合
+    pub fn is_standalone(&self) -> bool {
+        matches!(
+            self,
+            Trigger::Corner { .. } | Trigger::Region { .. } | Trigger::Typed(_)
+        )
+    }
+
+    /// 按键对应的触发按键(修饰键同时对应不区分左右的修饰键)
+    pub fn of_key(key: Key) -> Vec<Self> {
+        let mut triggers = vec![Trigger::Key(key)];
+        triggers.extend(Modifier::of(key).map(Trigger::Modifier));
+        triggers
+    }
+}
+
+/// 反序列化触发按键: 快捷键字符串或列表(元素可以是快捷键字符串)
+///
+/// 按输入形状选择格式, 保留按键名称的错误信息
+pub fn deserialize_triggers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Trigger>, D::Error> {
+    deserializer.deserialize_any(TriggersVisitor { list: true })
+}
+
+/// 触发按键列表的元素: 快捷键字符串或单个触发
+struct TriggerItem(Vec<Trigger>);
+
+impl<'de> Deserialize<'de> for TriggerItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(TriggersVisitor { list: false })
+            .map(TriggerItem)
+    }
+}
+
+/// list 为 false 时不接受嵌套列表
+struct TriggersVisitor {
+    list: bool,
+}
+
+impl<'de> de::Visitor<'de> for TriggersVisitor {
+    type Value = Vec<Trigger>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.list {
+            true => f.write_str("快捷键字符串、触发按键或它们的列表"),
+            false => f.write_str("快捷键字符串或触发按键"),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<Trigger>, E> {
+        Trigger::parse(v).map_err(E::custom)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<Trigger>, A::Error> {
+        if !self.list {
+            return Err(de::Error::invalid_type(de::Unexpected::Seq, &self));
+        }
+        let mut triggers = vec![];
+        while let Some(TriggerItem(item)) = seq.next_element()? {
+            triggers.extend(item);
+        }
+        Ok(triggers)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Vec<Trigger>, A::Error> {
+        Trigger::deserialize(de::value::MapAccessDeserializer::new(map)).map(|trigger| vec![trigger])
+    }
 }
 
 /// 自定义事件
@@ -162,13 +977,72 @@ pub enum Custom {
 
     /// 退出
     Exit(i32),
+
+    /// 相对当前位置移动鼠标(已缩放)
+    MoveBy(f64, f64),
+
+    /// 移动鼠标到运行时计算的位置
+    Move(Position),
+
+    /// 在 duration 毫秒内沿曲线移动鼠标到指定位置, 每秒发送 rate 次移动事件
+    MoveTo {
+        to: Position,
+        duration: u64,
+        curve: Curve,
+        rate: u64,
+    },
+
+    /// 调用其他脚本, wait 为 true 时等待其结束
+    Run { title: String, wait: bool },
 }
 
 impl Custom {
-    pub async fn run(&self) {
+    /// 执行自定义事件, 返回 false 表示脚本应当停止
+    pub async fn run(&self, context: &Context) -> bool {
         match self {
-            Custom::Sleep(n) => tokio::time::sleep(Duration::from_millis(*n)).await,
-            Custom::Exit(code) => exit(*code),
+            Custom::Sleep(n) => {
+                tokio::time::sleep(Duration::from_millis(*n)).await;
+                true
+            }
+            Custom::Exit(code) => {
+                let _ = context.exiter.send(*code);
+                false
+            }
+            Custom::MoveBy(dx, dy) => {
+                let (x, y) = context.locate();
+                let (x, y) = (x + dx, y + dy);
+                mouse_move(x, y, context);
+                tokio::time::sleep(Duration::from_micros(100)).await;
+                true
+            }
+            Custom::Move(position) => {
+                let (x, y) = position.resolve();
+                mouse_move(x, y, context);
+                tokio::time::sleep(Duration::from_micros(100)).await;
+                true
+            }
+            Custom::MoveTo { to, duration, curve, rate } => {
+                let from = context.locate();
+                let steps = (duration * rate / 1000).max(1);
+                let interval = Duration::from_millis(*duration) / steps as u32;
+                for (x, y) in curve.path(from, to.resolve(), steps) {
+                    mouse_move(x, y, context);
+                    tokio::time::sleep(interval.max(Duration::from_micros(100))).await;
+                }
+                true
+            }
+            Custom::Run { title, wait } => {
+                let (responder, response) = oneshot::channel();
+                let _ = context.commander.send(Command::Run(title.clone(), responder));
+                match response.await {
+                    Ok(Some(mut done)) if *wait => {
+                        let _ = done.changed().await;
+                    }
+                    Ok(None) => println!("没有找到标题为 {title:?} 的脚本"),
+                    _ => {}
+                }
+                true
+            }
         }
     }
 }
@@ -177,8 +1051,17 @@ impl Custom {
 pub enum Method {
     /// 事件
     Event(EventType),
+    /// 同时执行多个事件, 之后等待 hold 毫秒(默认使用脚本延迟)
+    Press { events: Vec<EventType>, hold: Option<u64> },
     /// 自定义
     Custom(Custom),
+    /// 循环执行, repeat 为 0 时不会停止
+    Loop { repeat: usize, methods: Arc<Vec<Method>> },
+    /// 调用命名脚本块
+    Call {
+        name: Arc<String>,
+        methods: Arc<Vec<Method>>,
+    },
 }
 
 impl Method {
@@ -194,4 +1077,344 @@ impl Method {
     pub fn mouse_up(button: Button) -> Self {
         Self::Event(EventType::ButtonRelease(button))
     }
+
+    /// 点击按键, hold 为按住时间(默认使用脚本延迟)
+    pub fn key(key: Key, hold: Option<u64>) -> [Self; 2] {
+        Self::press(EventType::KeyPress(key), EventType::KeyRelease(key), hold)
+    }
+
+    /// 点击鼠标, hold 为按住时间(默认使用脚本延迟)
+    pub fn click(button: Button, hold: Option<u64>) -> [Self; 2] {
+        Self::press(EventType::ButtonPress(button), EventType::ButtonRelease(button), hold)
+    }
+
+    fn press(down: EventType, up: EventType, hold: Option<u64>) -> [Self; 2] {
+        match hold {
+            Some(_) => [Self::Press { events: vec![down], hold }, Self::Event(up)],
+            None => [Self::Event(down), Self::Event(up)],
+        }
+    }
+
+    /// 是否包含 repeat = 0 的循环
+    pub fn endless(&self) -> bool {
+        match self {
+            Method::Loop { repeat: 0, .. } => true,
+            Method::Loop { methods, .. } | Method::Call { methods, .. } => methods.iter().any(Method::endless),
+            _ => false,
+        }
+    }
+
+    /// 调用并等待结束的脚本
+    pub fn waited(&self) -> Vec<&str> {
+        match self {
+            Method::Custom(Custom::Run { title, wait: true }) => vec![title],
+            Method::Loop { methods, .. } | Method::Call { methods, .. } => {
+                methods.iter().flat_map(Method::waited).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Triggers {
+        #[serde(deserialize_with = "deserialize_triggers")]
+        trigger: Vec<Trigger>,
+    }
+
+    fn triggers(data: &str) -> Result<Vec<Trigger>, String> {
+        toml::from_str::<Triggers>(data)
+            .map(|triggers| triggers.trigger)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn triggers_by_shape() {
+        assert_eq!(
+            triggers(r#"trigger = "Ctrl+A""#).unwrap(),
+            [Trigger::Modifier(Modifier::Ctrl), Trigger::Key(Key::KeyA)]
+        );
+        assert_eq!(
+            triggers(r#"trigger = ["Ctrl+A", { Key = "Home" }, { Mouse = "Mouse4" }]"#).unwrap(),
+            [
+                Trigger::Modifier(Modifier::Ctrl),
+                Trigger::Key(Key::KeyA),
+                Trigger::Key(Key::Home),
+                Trigger::Mouse(keys::SIDE_BUTTONS[0].1),
+            ]
+        );
+        assert_eq!(
+            triggers(r#"trigger = { Wheel = "Up" }"#).unwrap(),
+            [Trigger::Wheel(Wheel::Up)]
+        );
+        assert!(triggers("trigger = [[\"A\"]]").is_err());
+    }
+
+    #[test]
+    fn trigger_errors_keep_suggestion() {
+        let suggestion = "无效的按键 \"Hmoe\", 是否是 \"Home\"?";
+        assert!(triggers(r#"trigger = [{ Key = "Hmoe" }]"#)
+            .unwrap_err()
+            .contains(suggestion));
+        assert!(triggers(r#"trigger = { Key = "Hmoe" }"#)
+            .unwrap_err()
+            .contains(suggestion));
+        assert!(triggers(r#"trigger = ["Ctrl+Hmoe"]"#).unwrap_err().contains(suggestion));
+    }
+
+    fn press(key: Key, name: Option<&str>) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: name.map(str::to_string),
+            event_type: EventType::KeyPress(key),
+        }
+    }
+
+    fn type_text(typing: &mut Typing, pressed: &Pressed, text: &str) {
+        for c in text.chars() {
+            assert!(typing.update(&press(Key::KeyA, Some(&c.to_string())), pressed));
+        }
+    }
+
+    fn options(text: &str, word: bool, ignore_case: bool) -> Typed {
+        Typed::Options { text: text.to_string(), word, ignore_case }
+    }
+
+    #[test]
+    fn typing_word_and_case() {
+        let mut typing = Typing::default();
+        type_text(&mut typing, &Pressed::default(), "hello;Sig");
+        assert!(typing.ends_with(&Typed::Text(";Sig".to_string())));
+        assert!(!typing.ends_with(&Typed::Text(";sig".to_string())));
+        assert!(typing.ends_with(&options(";sig", false, true)));
+        assert!(typing.ends_with(&options("Sig", true, false)));
+        type_text(&mut typing, &Pressed::default(), " dosig");
+        assert!(typing.ends_with(&options("sig", false, false)));
+        assert!(!typing.ends_with(&options("sig", true, false)));
+    }
+
+    #[test]
+    fn typing_backspace_and_modifiers() {
+        let mut typing = Typing::default();
+        let mut pressed = Pressed::default();
+        type_text(&mut typing, &pressed, "abc");
+        assert!(!typing.update(&press(Key::Backspace, None), &pressed));
+        assert_eq!(typing.text, "ab");
+
+        // Shift 输入大写字母, 修饰键本身不改变文本
+        pressed.update(&EventType::KeyPress(Key::ShiftLeft));
+        assert!(!typing.update(&press(Key::ShiftLeft, None), &pressed));
+        type_text(&mut typing, &pressed, "C");
+        assert_eq!(typing.text, "abC");
+        pressed.update(&EventType::KeyRelease(Key::ShiftLeft));
+
+        for modifier in [Key::ControlLeft, Key::Alt, Key::MetaRight] {
+            type_text(&mut typing, &pressed, "abc");
+            pressed.update(&EventType::KeyPress(modifier));
+            assert!(!typing.update(&press(Key::KeyV, Some("v")), &pressed));
+            assert_eq!(typing.text, "", "{modifier:?}");
+            pressed.update(&EventType::KeyRelease(modifier));
+        }
+    }
+
+    #[test]
+    fn typing_clears_on_clicks_and_control_keys() {
+        let mut typing = Typing::default();
+        let pressed = Pressed::default();
+        type_text(&mut typing, &pressed, "abc");
+        let click = Event {
+            time: SystemTime::now(),
+            name: None,
+            event_type: EventType::ButtonPress(Button::Left),
+        };
+        assert!(!typing.update(&click, &pressed));
+        assert_eq!(typing.text, "");
+
+        type_text(&mut typing, &pressed, "abc");
+        assert!(!typing.update(&press(Key::Return, Some("\r")), &pressed));
+        assert_eq!(typing.text, "");
+        type_text(&mut typing, &pressed, "abc");
+        assert!(!typing.update(&press(Key::UpArrow, None), &pressed));
+        assert_eq!(typing.text, "");
+    }
+
+    #[test]
+    fn typing_keeps_last_characters() {
+        let mut typing = Typing::default();
+        let text: String = ('a'..='z').cycle().take(Typing::CAPACITY + 6).collect();
+        type_text(&mut typing, &Pressed::default(), &text);
+        assert_eq!(typing.text.chars().count(), Typing::CAPACITY);
+        assert!(text.ends_with(&typing.text));
+    }
+
+    fn pressed(keys: &[Key]) -> Pressed {
+        let mut pressed = Pressed::default();
+        for key in keys {
+            pressed.update(&EventType::KeyPress(*key));
+        }
+        pressed
+    }
+
+    #[test]
+    fn exact_chord_rejects_extra_modifiers() {
+        let trigger = Trigger::parse("Ctrl+A").unwrap();
+        let key = Trigger::Key(Key::KeyA);
+        let chord = pressed(&[Key::ControlLeft, Key::KeyA]);
+        assert!(chord.matches(&trigger, &key, true));
+        let shifted = pressed(&[Key::ControlLeft, Key::ShiftLeft, Key::KeyA]);
+        assert!(shifted.matches(&trigger, &key, false));
+        assert!(!shifted.matches(&trigger, &key, true));
+        assert!(!pressed(&[Key::KeyA]).matches(&trigger, &key, false));
+        assert!(!chord.matches(&trigger, &Trigger::Key(Key::KeyB), false));
+    }
+
+    #[test]
+    fn side_agnostic_modifier_is_part_of_chord() {
+        let trigger = Trigger::parse("Ctrl+A").unwrap();
+        let key = Trigger::Key(Key::KeyA);
+        let right = pressed(&[Key::ControlRight, Key::KeyA]);
+        assert!(right.matches(&trigger, &key, true));
+        // 指定左侧时右侧不满足, 精确匹配时右侧是多余的修饰键
+        let left = [Trigger::Key(Key::ControlLeft), key.clone()];
+        assert!(!right.matches(&left, &key, false));
+        let both = pressed(&[Key::ControlLeft, Key::ControlRight, Key::KeyA]);
+        assert!(both.matches(&left, &key, false));
+        assert!(!both.matches(&left, &key, true));
+        assert!(both.matches(&trigger, &key, true));
+    }
+
+    fn script(accelerator: &str, on: TriggerOn) -> (Script, UnboundedReceiver<Title>) {
+        let (updater, titles) = mpsc::unbounded_channel();
+        let script = Script {
+            title: Arc::new("测试".to_string()),
+            delay: 0,
+            repeat: 1,
+            methods: Arc::new(vec![]),
+            task: None,
+            trigger: Trigger::parse(accelerator).unwrap(),
+            exact: false,
+            on,
+            consume: false,
+            armed: false,
+            hover: None,
+            updater,
+            context: Context {
+                exiter: mpsc::unbounded_channel().0,
+                commander: mpsc::unbounded_channel().0,
+                cursor: Default::default(),
+            },
+            done: None,
+        };
+        (script, titles)
+    }
+
+    /// 与脚本列表相同, 先更新按住的按键再通知脚本
+    fn send(script: &mut Script, pressed: &mut Pressed, event_type: EventType) {
+        pressed.update(&event_type);
+        match event_type {
+            EventType::KeyPress(key) => Trigger::of_key(key)
+                .iter()
+                .for_each(|trigger| script.down(trigger, pressed)),
+            EventType::KeyRelease(key) => Trigger::of_key(key)
+                .iter()
+                .for_each(|trigger| script.up(trigger, pressed)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn release_runs_after_all_keys_released() {
+        let (mut script, mut titles) = script("Ctrl+A", TriggerOn::Release);
+        let mut pressed = Pressed::default();
+        send(&mut script, &mut pressed, EventType::KeyPress(Key::ControlRight));
+        send(&mut script, &mut pressed, EventType::KeyPress(Key::KeyA));
+        assert!(script.armed);
+        send(&mut script, &mut pressed, EventType::KeyRelease(Key::KeyA));
+        assert!(titles.try_recv().is_err());
+        send(&mut script, &mut pressed, EventType::KeyRelease(Key::ControlRight));
+        assert!(!script.armed);
+        assert_eq!(titles.try_recv().unwrap(), (script.title.clone(), true));
+
+        // 没有按住全部触发按键时不会运行
+        send(&mut script, &mut pressed, EventType::KeyPress(Key::KeyA));
+        send(&mut script, &mut pressed, EventType::KeyRelease(Key::KeyA));
+        assert!(!script.armed);
+        assert!(titles.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn press_runs_immediately() {
+        let (mut script, mut titles) = script("Ctrl+A", TriggerOn::Press);
+        let mut pressed = Pressed::default();
+        send(&mut script, &mut pressed, EventType::KeyPress(Key::ControlLeft));
+        assert!(titles.try_recv().is_err());
+        send(&mut script, &mut pressed, EventType::KeyPress(Key::KeyA));
+        assert!(!script.armed);
+        assert_eq!(titles.try_recv().unwrap(), (script.title.clone(), true));
+    }
+
+    fn remap(from: Key, to: &[Key]) -> Remap {
+        let events = |key: Key| (EventType::KeyPress(key), EventType::KeyRelease(key));
+        Remap {
+            from: events(from),
+            to: to.iter().map(|key| events(*key)).collect(),
+        }
+    }
+
+    #[test]
+    fn remap_skips_own_events() {
+        let mut interceptor = Interceptor::new(vec![
+            remap(Key::CapsLock, &[Key::Escape]),
+            remap(Key::Escape, &[Key::CapsLock]),
+        ]);
+        let caps = EventType::KeyPress(Key::CapsLock);
+        let escape = EventType::KeyPress(Key::Escape);
+        assert_eq!(interceptor.remap(&caps), Some(vec![escape]));
+        // 模拟的 Escape 再次被监听到时不会被重映射回 CapsLock
+        assert_eq!(interceptor.remap(&escape), None);
+        assert_eq!(interceptor.remap(&escape), Some(vec![caps]));
+        assert_eq!(interceptor.remap(&EventType::KeyPress(Key::KeyA)), None);
+    }
+
+    #[test]
+    fn remap_pending_expires() {
+        let mut interceptor = Interceptor::new(vec![remap(Key::Escape, &[Key::CapsLock])]);
+        let escape = EventType::KeyPress(Key::Escape);
+        interceptor
+            .pending
+            .push_back((escape, Instant::now() - Interceptor::PENDING));
+        assert_eq!(
+            interceptor.remap(&escape),
+            Some(vec![EventType::KeyPress(Key::CapsLock)])
+        );
+        assert_eq!(interceptor.pending.len(), 1);
+    }
+
+    #[test]
+    fn remap_releases_in_reverse() {
+        let remap = remap(Key::CapsLock, &[Key::ControlLeft, Key::ShiftLeft, Key::Escape]);
+        assert_eq!(
+            remap.events(&EventType::KeyPress(Key::CapsLock)),
+            Some(vec![
+                EventType::KeyPress(Key::ControlLeft),
+                EventType::KeyPress(Key::ShiftLeft),
+                EventType::KeyPress(Key::Escape),
+            ])
+        );
+        assert_eq!(
+            remap.events(&EventType::KeyRelease(Key::CapsLock)),
+            Some(vec![
+                EventType::KeyRelease(Key::Escape),
+                EventType::KeyRelease(Key::ShiftLeft),
+                EventType::KeyRelease(Key::ControlLeft),
+            ])
+        );
+        assert_eq!(remap.events(&EventType::KeyPress(Key::Escape)), None);
+    }
 }
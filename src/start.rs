@@ -1,16 +1,20 @@
 use std::{env, fs, fs::File, io, io::Write, os::windows::fs::OpenOptionsExt, path::PathBuf, process, time::Duration};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// 通过独享 lock 文件删写权限实现的单例程序
 ///
 /// 此数据被 drop 将释放权限
 #[cfg(target_os = "windows")]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Restart {
+    /// 运行上次启动的程序
     Prev,
+    /// 运行本次启动的程序
     Curr,
+    /// 允许多开
     Many,
 }
 
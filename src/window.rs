@@ -5,7 +5,7 @@ use druid::{
     widget::{CrossAxisAlignment, Flex, Label},
     *,
 };
-use tokio::sync::{mpsc, mpsc::UnboundedSender};
+use tokio::sync::{broadcast, mpsc, mpsc::UnboundedSender};
 
 use crate::script::Title;
 
@@ -16,6 +16,8 @@ pub struct WindowList {
     pub app: AppLauncher<AppData>,
     pub app_data: AppData,
     pub updater: UnboundedSender<Title>,
+    /// 转发脚本状态变化给其他订阅者
+    pub states: broadcast::Sender<Title>,
 }
 unsafe impl Send for WindowList {}
 
@@ -49,15 +51,18 @@ impl WindowList {
         });
 
         let ext = app.get_external_handle();
+        let (states, _) = broadcast::channel::<Title>(64);
+        let sender = states.clone();
         tokio::spawn(async move {
             while let Some((title, state)) = rx.recv().await {
+                let _ = sender.send((title.clone(), state));
                 ext.add_idle_callback(move |data: &mut AppData| {
                     data.titles.insert(title, state);
                 });
             }
         });
 
-        Self { app, app_data: AppData::default(), updater }
+        Self { app, app_data: AppData::default(), updater, states }
     }
 }
 